@@ -0,0 +1,68 @@
+/*
+ * Skyrim Search SE C API
+ *
+ * Read access to the search index (npc, actor, cell, quest, quest_stage, plugin tables; see
+ * index/src/migration.rs) for other SKSE plugins. Either resolve the exports from
+ * skyrim_search_se.dll with GetProcAddress, or register an SKSE messaging listener for
+ * "Skyrim Search SE" and wait for SKYRIM_SEARCH_MESSAGE_API_READY, whose data points to a
+ * SkyrimSearchApi. It is sent on SKSE's kMessage_PostPostLoad, so register the listener in your
+ * kMessage_PostLoad handler at the latest.
+ *
+ * The index is built while the game loads data and saves, so results depend on when you ask.
+ */
+#ifndef SKYRIM_SEARCH_H
+#define SKYRIM_SEARCH_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SKYRIM_SEARCH_API_VERSION 1
+
+/* 'SSE0' */
+#define SKYRIM_SEARCH_MESSAGE_API_READY 0x53534530
+
+#define SKYRIM_SEARCH_OK 0
+#define SKYRIM_SEARCH_INVALID_ARGUMENT (-1)
+#define SKYRIM_SEARCH_NOT_FOUND (-2)
+#define SKYRIM_SEARCH_QUERY_FAILED (-3)
+
+/*
+ * Called once per row; return false to stop. names and values hold column_count entries,
+ * values are text (integers in decimal) and NULL for SQL NULL. Nothing outlives the call.
+ * The callback may call the other functions of this API.
+ */
+typedef bool (*SkyrimSearchRowCallback)(void* userdata, uint32_t column_count,
+                                        const char* const* names, const char* const* values);
+
+/* Runs a read-only SQL query. Returns SKYRIM_SEARCH_OK or a negative error. */
+typedef int32_t (*SkyrimSearchQuery)(const char* sql, SkyrimSearchRowCallback callback,
+                                     void* userdata);
+
+/*
+ * Copies the name / editor id of a form or reference into buf (truncated, always NUL
+ * terminated if len > 0). Returns the full length without the NUL, or a negative error.
+ * Pass buf = NULL to only get the length.
+ */
+typedef int32_t (*SkyrimSearchLookup)(uint32_t form_id, char* buf, uint32_t len);
+
+typedef struct SkyrimSearchApi {
+    uint32_t version;
+    SkyrimSearchQuery query;
+    SkyrimSearchLookup lookup_name;
+    SkyrimSearchLookup lookup_editor_id;
+} SkyrimSearchApi;
+
+uint32_t SkyrimSearch_GetApiVersion(void);
+int32_t SkyrimSearch_Query(const char* sql, SkyrimSearchRowCallback callback, void* userdata);
+int32_t SkyrimSearch_LookupName(uint32_t form_id, char* buf, uint32_t len);
+int32_t SkyrimSearch_LookupEditorId(uint32_t form_id, char* buf, uint32_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SKYRIM_SEARCH_H */
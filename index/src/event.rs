@@ -0,0 +1,382 @@
+use anyhow::{anyhow, Context};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Something the game told us about a form, in the order the hooks saw it.
+///
+/// Events are recorded one per line as `kind<TAB>fields...` (see `Display`/`FromStr`), with
+/// form ids in hex, `\N` for a missing string and `\\`, `\t`, `\n`, `\r` escaped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexEvent {
+    NpcEditorId {
+        form_id: u32,
+        editor_id: String,
+    },
+    NpcName {
+        form_id: u32,
+        name: String,
+    },
+    Cell {
+        form_id: u32,
+        editor_id: Option<String>,
+        name: Option<String>,
+    },
+    Quest {
+        form_id: u32,
+        editor_id: Option<String>,
+        name: Option<String>,
+    },
+    QuestStage {
+        form_id: u32,
+        stage: u16,
+        log: u32,
+    },
+    ActorRef {
+        form_id: u32,
+        base_form_id: u32,
+    },
+    /// A loaded plugin; `id` is its load order index, or FExxx for a light plugin.
+    Plugin {
+        id: u32,
+        file_name: String,
+    },
+    /// A form found by enumerating the data handler. Unlike `Cell` and `Quest`, a missing
+    /// editor id or name keeps what the `Load` hooks already stored.
+    EnumeratedForm {
+        table: FormTable,
+        form_id: u32,
+        editor_id: Option<String>,
+        name: Option<String>,
+    },
+}
+
+/// The tables with a form_id, editor_id and name column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormTable {
+    Npc,
+    Cell,
+    Quest,
+}
+
+impl FormTable {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FormTable::Npc => "npc",
+            FormTable::Cell => "cell",
+            FormTable::Quest => "quest",
+        }
+    }
+}
+
+impl fmt::Display for FormTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for FormTable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "npc" => Ok(FormTable::Npc),
+            "cell" => Ok(FormTable::Cell),
+            "quest" => Ok(FormTable::Quest),
+            _ => anyhow::bail!("unknown table {:?}", s),
+        }
+    }
+}
+
+impl IndexEvent {
+    fn kind(&self) -> &'static str {
+        match self {
+            IndexEvent::NpcEditorId { .. } => "npc_edid",
+            IndexEvent::NpcName { .. } => "npc_name",
+            IndexEvent::Cell { .. } => "cell",
+            IndexEvent::Quest { .. } => "quest",
+            IndexEvent::QuestStage { .. } => "quest_stage",
+            IndexEvent::ActorRef { .. } => "actor",
+            IndexEvent::Plugin { .. } => "plugin",
+            IndexEvent::EnumeratedForm { .. } => "enumerated",
+        }
+    }
+
+    // Events with the same key write the same row (or column), so only the last one counts.
+    fn key(&self) -> (&'static str, u32, u16) {
+        match *self {
+            IndexEvent::NpcEditorId { form_id, .. }
+            | IndexEvent::NpcName { form_id, .. }
+            | IndexEvent::Cell { form_id, .. }
+            | IndexEvent::Quest { form_id, .. }
+            | IndexEvent::ActorRef { form_id, .. }
+            | IndexEvent::Plugin { id: form_id, .. }
+            | IndexEvent::EnumeratedForm { form_id, .. } => (self.kind(), form_id, 0),
+            IndexEvent::QuestStage { form_id, stage, .. } => (self.kind(), form_id, stage),
+        }
+    }
+}
+
+/// Drops every event that a later one overwrites anyway. The rest keep their order.
+pub fn dedup(events: Vec<IndexEvent>) -> Vec<IndexEvent> {
+    let mut seen = HashSet::new();
+    let mut events: Vec<IndexEvent> = events
+        .into_iter()
+        .rev()
+        .filter(|event| seen.insert(event.key()))
+        .collect();
+    events.reverse();
+    events
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> anyhow::Result<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            other => anyhow::bail!("invalid escape \\{}", other.unwrap_or(' ')),
+        }
+    }
+    Ok(unescaped)
+}
+
+struct Field<'a>(&'a Option<String>);
+
+impl fmt::Display for Field<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(s) => write!(f, "{}", escape(s)),
+            None => write!(f, "\\N"),
+        }
+    }
+}
+
+impl fmt::Display for IndexEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t", self.kind())?;
+        match self {
+            IndexEvent::NpcEditorId { form_id, editor_id } => {
+                write!(f, "{:08X}\t{}", form_id, escape(editor_id))
+            }
+            IndexEvent::NpcName { form_id, name } => write!(f, "{:08X}\t{}", form_id, escape(name)),
+            IndexEvent::Cell {
+                form_id,
+                editor_id,
+                name,
+            }
+            | IndexEvent::Quest {
+                form_id,
+                editor_id,
+                name,
+            } => write!(f, "{:08X}\t{}\t{}", form_id, Field(editor_id), Field(name)),
+            IndexEvent::QuestStage {
+                form_id,
+                stage,
+                log,
+            } => write!(f, "{:08X}\t{}\t{}", form_id, stage, log),
+            IndexEvent::ActorRef {
+                form_id,
+                base_form_id,
+            } => write!(f, "{:08X}\t{:08X}", form_id, base_form_id),
+            IndexEvent::Plugin { id, file_name } => write!(f, "{:05X}\t{}", id, escape(file_name)),
+            IndexEvent::EnumeratedForm {
+                table,
+                form_id,
+                editor_id,
+                name,
+            } => write!(
+                f,
+                "{}\t{:08X}\t{}\t{}",
+                table,
+                form_id,
+                Field(editor_id),
+                Field(name)
+            ),
+        }
+    }
+}
+
+fn next_field<'a>(fields: &mut impl Iterator<Item = &'a str>) -> anyhow::Result<&'a str> {
+    fields.next().ok_or_else(|| anyhow!("missing field"))
+}
+
+fn parse_form_id(field: &str) -> anyhow::Result<u32> {
+    u32::from_str_radix(field, 16).with_context(|| format!("invalid form id {}", field))
+}
+
+fn parse_optional(field: &str) -> anyhow::Result<Option<String>> {
+    match field {
+        "\\N" => Ok(None),
+        field => unescape(field).map(Some),
+    }
+}
+
+impl FromStr for IndexEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> anyhow::Result<Self> {
+        let mut fields = line.split('\t');
+        let kind = next_field(&mut fields)?;
+        let mut next = || next_field(&mut fields);
+
+        let event = match kind {
+            "npc_edid" => IndexEvent::NpcEditorId {
+                form_id: parse_form_id(next()?)?,
+                editor_id: unescape(next()?)?,
+            },
+            "npc_name" => IndexEvent::NpcName {
+                form_id: parse_form_id(next()?)?,
+                name: unescape(next()?)?,
+            },
+            "cell" => IndexEvent::Cell {
+                form_id: parse_form_id(next()?)?,
+                editor_id: parse_optional(next()?)?,
+                name: parse_optional(next()?)?,
+            },
+            "quest" => IndexEvent::Quest {
+                form_id: parse_form_id(next()?)?,
+                editor_id: parse_optional(next()?)?,
+                name: parse_optional(next()?)?,
+            },
+            "quest_stage" => IndexEvent::QuestStage {
+                form_id: parse_form_id(next()?)?,
+                stage: next()?.parse().context("invalid stage")?,
+                log: next()?.parse().context("invalid log")?,
+            },
+            "actor" => IndexEvent::ActorRef {
+                form_id: parse_form_id(next()?)?,
+                base_form_id: parse_form_id(next()?)?,
+            },
+            "plugin" => IndexEvent::Plugin {
+                id: parse_form_id(next()?)?,
+                file_name: unescape(next()?)?,
+            },
+            "enumerated" => IndexEvent::EnumeratedForm {
+                table: next()?.parse()?,
+                form_id: parse_form_id(next()?)?,
+                editor_id: parse_optional(next()?)?,
+                name: parse_optional(next()?)?,
+            },
+            _ => anyhow::bail!("unknown event {:?}", kind),
+        };
+        if next().is_ok() {
+            anyhow::bail!("too many fields");
+        }
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let events = vec![
+            IndexEvent::NpcEditorId {
+                form_id: 0xA2C8E,
+                editor_id: String::from("HousecarlWhiterun"),
+            },
+            IndexEvent::NpcName {
+                form_id: 0xA2C8E,
+                name: String::from("Tab\there \\N"),
+            },
+            IndexEvent::Cell {
+                form_id: 0x165A8,
+                editor_id: Some(String::from("WhiterunBreezehome")),
+                name: None,
+            },
+            IndexEvent::Quest {
+                form_id: 0xE4D31,
+                editor_id: None,
+                name: Some(String::new()),
+            },
+            IndexEvent::QuestStage {
+                form_id: 0xE4D31,
+                stage: 150,
+                log: 6,
+            },
+            IndexEvent::ActorRef {
+                form_id: 0xA2C94,
+                base_form_id: 0xA2C8E,
+            },
+            IndexEvent::Plugin {
+                id: 0xFE001,
+                file_name: String::from("ccBGSSSE001-Fish.esm"),
+            },
+            IndexEvent::EnumeratedForm {
+                table: FormTable::Npc,
+                form_id: 0xA2C8E,
+                editor_id: Some(String::from("HousecarlWhiterun")),
+                name: None,
+            },
+        ];
+        for event in events {
+            let line = event.to_string();
+            assert!(!line.contains('\n'));
+            assert_eq!(event, line.parse().unwrap());
+        }
+        assert!("npc_name\t000A2C8E".parse::<IndexEvent>().is_err());
+        assert!("npc_name\tlydia\tLydia".parse::<IndexEvent>().is_err());
+        assert!("npc\t000A2C8E\tLydia".parse::<IndexEvent>().is_err());
+        assert!("enumerated\tactor\t000A2C8E\t\\N\t\\N"
+            .parse::<IndexEvent>()
+            .is_err());
+    }
+
+    #[test]
+    fn dedup_keeps_last() {
+        let name = |form_id, name: &str| IndexEvent::NpcName {
+            form_id,
+            name: String::from(name),
+        };
+        let edid = IndexEvent::NpcEditorId {
+            form_id: 1,
+            editor_id: String::from("A"),
+        };
+        let stage = |stage| IndexEvent::QuestStage {
+            form_id: 1,
+            stage,
+            log: 0,
+        };
+        assert_eq!(
+            vec![
+                edid.clone(),
+                stage(10),
+                name(2, "b"),
+                name(1, "c"),
+                stage(20)
+            ],
+            dedup(vec![
+                name(1, "a"),
+                edid,
+                stage(10),
+                name(2, "b"),
+                name(1, "c"),
+                stage(20),
+            ])
+        );
+    }
+}
@@ -0,0 +1,88 @@
+/// Candidates scoring below this are not worth showing.
+pub const MIN_SCORE: f64 = 0.5;
+
+// Optimal string alignment distance: Levenshtein plus adjacent transpositions, so that
+// "lyida" is one edit from "lydia" rather than two.
+fn osa_distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+fn similarity(a: &[char], b: &[char]) -> f64 {
+    let len = a.len().max(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+    1.0 - osa_distance(a, b) as f64 / len as f64
+}
+
+/// How close `query` is to `text` or to one of its words, from 0 (nothing alike) to 1.
+/// Registered as the SQL function fuzzy_score(query, text).
+pub fn score(query: &str, text: &str) -> f64 {
+    let query: Vec<char> = query.trim().to_lowercase().chars().collect();
+    crate::split_words(text)
+        .split_whitespace()
+        .chain(std::iter::once(text))
+        .map(|candidate| {
+            let candidate: Vec<char> = candidate.trim().to_lowercase().chars().collect();
+            similarity(&query, &candidate)
+        })
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        osa_distance(&a, &b)
+    }
+
+    #[test]
+    fn osa() {
+        assert_eq!(0, distance("lydia", "lydia"));
+        assert_eq!(1, distance("lyida", "lydia"));
+        assert_eq!(2, distance("lidya", "lydia"));
+        assert_eq!(1, distance("breezhome", "breezehome"));
+        assert_eq!(5, distance("", "lydia"));
+    }
+
+    #[test]
+    fn misspellings() {
+        assert_eq!(1.0, score("lydia", "Lydia"));
+        assert_eq!(0.8, score("lyida", "Lydia"));
+        assert_eq!(0.6, score("lidya", "Lydia"));
+        assert_eq!(0.9, score("breezhome", "Breezehome"));
+        // against a word of an editor id
+        assert_eq!(1.0, score("whiterun", "HousecarlWhiterun"));
+        assert_eq!(0.875, score("whitrun", "HousecarlWhiterun"));
+    }
+
+    #[test]
+    fn cut_off() {
+        assert!(score("lidya", "Lydia") >= MIN_SCORE);
+        assert!(score("breezhome", "Breezehome") >= MIN_SCORE);
+        assert!(score("lydia", "Ulfric Stormcloak") < MIN_SCORE);
+        assert!(score("breezhome", "Dragonsreach") < MIN_SCORE);
+        assert_eq!(0.0, score("", ""));
+    }
+}
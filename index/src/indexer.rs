@@ -0,0 +1,261 @@
+use crate::event::{dedup, FormTable, IndexEvent};
+use anyhow::Context;
+use rusqlite::params;
+use std::io::BufRead;
+
+macro_rules! upsert_form_sql {
+    ($table:literal) => {
+        concat!(
+            "INSERT INTO ",
+            $table,
+            " (form_id, editor_id, name) VALUES (?, ?, ?) \
+             ON CONFLICT(form_id) DO UPDATE SET \
+             editor_id=COALESCE(excluded.editor_id, editor_id), \
+             name=COALESCE(excluded.name, name)"
+        )
+    };
+}
+
+/// Writes `IndexEvent`s to a db migrated by `migration::migrate`. The only place that knows
+/// how an event maps to rows.
+pub struct Indexer<'a> {
+    db: &'a rusqlite::Connection,
+}
+
+impl<'a> Indexer<'a> {
+    pub fn new(db: &'a rusqlite::Connection) -> Self {
+        Indexer { db }
+    }
+
+    pub fn apply(&self, event: &IndexEvent) -> anyhow::Result<()> {
+        let result = match event {
+            // the npc hooks see the editor id and the name at different times
+            IndexEvent::NpcEditorId { form_id, editor_id } => self.execute(
+                "INSERT INTO npc (form_id, editor_id) VALUES (?, ?) \
+                 ON CONFLICT(form_id) DO UPDATE SET editor_id=excluded.editor_id",
+                params![form_id, editor_id],
+            ),
+            IndexEvent::NpcName { form_id, name } => self.execute(
+                "INSERT INTO npc (form_id, name) VALUES (?, ?) \
+                 ON CONFLICT(form_id) DO UPDATE SET name=excluded.name",
+                params![form_id, name],
+            ),
+            IndexEvent::Cell {
+                form_id,
+                editor_id,
+                name,
+            } => self.execute(
+                "INSERT OR REPLACE INTO cell (form_id, editor_id, name) VALUES (?, ?, ?)",
+                params![form_id, editor_id, name],
+            ),
+            IndexEvent::Quest {
+                form_id,
+                editor_id,
+                name,
+            } => self.execute(
+                "INSERT OR REPLACE INTO quest (form_id, editor_id, name) VALUES (?, ?, ?)",
+                params![form_id, editor_id, name],
+            ),
+            IndexEvent::QuestStage {
+                form_id,
+                stage,
+                log,
+            } => self.execute(
+                "INSERT OR REPLACE INTO quest_stage (form_id, stage, log) VALUES (?, ?, ?)",
+                params![form_id, stage, log],
+            ),
+            IndexEvent::ActorRef {
+                form_id,
+                base_form_id,
+            } => self.execute(
+                "INSERT OR REPLACE INTO actor (form_id, base_form_id) VALUES (?, ?)",
+                params![form_id, base_form_id],
+            ),
+            IndexEvent::Plugin { id, file_name } => self.execute(
+                "INSERT OR REPLACE INTO plugin (id, file_name) VALUES (?, ?)",
+                params![id, file_name],
+            ),
+            IndexEvent::EnumeratedForm {
+                table,
+                form_id,
+                editor_id,
+                name,
+            } => self.execute(
+                match table {
+                    FormTable::Npc => upsert_form_sql!("npc"),
+                    FormTable::Cell => upsert_form_sql!("cell"),
+                    FormTable::Quest => upsert_form_sql!("quest"),
+                },
+                params![form_id, editor_id, name],
+            ),
+        };
+        result.with_context(|| format!("index {}", event))
+    }
+
+    fn execute(&self, sql: &str, params: &[&dyn rusqlite::ToSql]) -> anyhow::Result<()> {
+        self.db
+            .prepare_cached(sql)
+            .context("prepare")?
+            .execute(params)
+            .context("execute")?;
+        Ok(())
+    }
+}
+
+/// Applies events recorded one per line (see `IndexEvent`) and returns how many were
+/// applied after deduplication. Empty lines and lines starting with `#` are skipped.
+pub fn replay<R: BufRead>(db: &rusqlite::Connection, reader: R) -> anyhow::Result<usize> {
+    let mut events = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.context("read")?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        events.push(
+            line.parse::<IndexEvent>()
+                .with_context(|| format!("line {}", i + 1))?,
+        );
+    }
+
+    let events = dedup(events);
+    let indexer = Indexer::new(db);
+    for event in events.iter() {
+        indexer.apply(event)?;
+    }
+    Ok(events.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration;
+    use rusqlite::NO_PARAMS;
+
+    fn open() -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA recursive_triggers=ON;").unwrap();
+        crate::register_functions(&conn).unwrap();
+        migration::migrate(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn replays_recorded_events() {
+        let conn = open();
+        let applied = replay(
+            &conn,
+            include_str!("../tests/fixtures/events.log").as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(10, applied);
+
+        // editor id and name came from separate events
+        let npc: (String, String, i64, String) = conn
+            .query_row(
+                "SELECT npc.editor_id, npc.name, actor.form_id, plugin.file_name FROM npc_fts \
+                 JOIN npc ON npc.form_id = npc_fts.rowid \
+                 JOIN actor ON actor.base_form_id = npc.form_id \
+                 JOIN plugin ON plugin.id = npc.plugin_id \
+                 WHERE npc_fts MATCH 'whiterun'",
+                NO_PARAMS,
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            (
+                String::from("HousecarlWhiterun"),
+                String::from("Lydia"),
+                0xA2C94,
+                String::from("Skyrim.esm")
+            ),
+            npc
+        );
+
+        // the cell was loaded twice; the second load wins
+        let cell: Option<String> = conn
+            .query_row(
+                "SELECT name FROM cell WHERE form_id = 0x165A8",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(Some(String::from("Breezehome")), cell);
+
+        let stages: Vec<(i64, i64)> = conn
+            .prepare(
+                "SELECT stage, log FROM quest \
+                 JOIN quest_stage ON quest.form_id = quest_stage.form_id ORDER BY stage",
+            )
+            .unwrap()
+            .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(vec![(30, 5), (150, 6)], stages);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let conn = open();
+        let err = replay(&conn, "npc_name\t000A2C8E\tLydia\nnpc\t1\n".as_bytes()).unwrap_err();
+        assert!(format!("{:#}", err).contains("line 2"));
+    }
+
+    #[test]
+    fn enumerated_forms_fill_in() {
+        let conn = open();
+        let indexer = Indexer::new(&conn);
+        let some = |s: &str| Some(String::from(s));
+        let enumerated = |table, form_id, editor_id, name| IndexEvent::EnumeratedForm {
+            table,
+            form_id,
+            editor_id,
+            name,
+        };
+        for event in [
+            IndexEvent::Cell {
+                form_id: 0x165A8,
+                editor_id: None,
+                name: some("Breezehome"),
+            },
+            IndexEvent::NpcName {
+                form_id: 0xA2C8E,
+                name: String::from("Lydia"),
+            },
+            // the data handler has no name for the cell and an editor id for it and the npc
+            enumerated(FormTable::Cell, 0x165A8, some("WhiterunBreezehome"), None),
+            enumerated(
+                FormTable::Npc,
+                0xA2C8E,
+                some("HousecarlWhiterun"),
+                some("Lydia"),
+            ),
+            // never seen by a load hook
+            enumerated(FormTable::Quest, 0xE4D31, some("dunGauldursonQST"), None),
+        ] {
+            indexer.apply(&event).unwrap();
+        }
+
+        let row = |sql: &str| -> (Option<String>, Option<String>) {
+            conn.query_row(sql, NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+        };
+        assert_eq!(
+            (some("WhiterunBreezehome"), some("Breezehome")),
+            row("SELECT editor_id, name FROM cell WHERE form_id = 0x165A8")
+        );
+        assert_eq!(
+            (some("HousecarlWhiterun"), some("Lydia")),
+            row("SELECT editor_id, name FROM npc WHERE form_id = 0xA2C8E")
+        );
+        assert_eq!(
+            (some("dunGauldursonQST"), None),
+            row("SELECT editor_id, name FROM quest WHERE form_id = 0xE4D31")
+        );
+        // and searchable like any other row
+        assert_eq!(
+            (some("Lydia"), some("HousecarlWhiterun Housecarl Whiterun")),
+            row("SELECT name, editor_id FROM npc_fts WHERE npc_fts MATCH 'housecarl'")
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! The search index behind skyrim-search-se: its schema, the SQL functions the schema
+//! relies on, the search queries, and the `Indexer` that writes `IndexEvent`s. Nothing here
+//! touches the game, so it builds and is tested on any platform.
+
+mod event;
+pub mod fuzzy;
+mod indexer;
+pub mod migration;
+pub mod search;
+
+pub use event::{dedup, FormTable, IndexEvent};
+pub use indexer::{replay, Indexer};
+
+use rusqlite::functions::FunctionFlags;
+
+/// "HousecarlWhiterun" -> "HousecarlWhiterun Housecarl Whiterun"
+pub fn split_words(editor_id: &str) -> String {
+    let mut words = vec![editor_id.to_string()];
+    let mut word = String::new();
+    let mut prev: Option<char> = None;
+    for c in editor_id.chars() {
+        let boundary = match prev {
+            Some(p) => {
+                (p.is_lowercase() && c.is_uppercase())
+                    || (p.is_alphabetic() && c.is_numeric())
+                    || (p.is_numeric() && c.is_alphabetic())
+            }
+            None => false,
+        };
+        if (!c.is_alphanumeric() || boundary) && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if c.is_alphanumeric() {
+            word.push(c);
+        }
+        prev = Some(c);
+    }
+    if !word.is_empty() && word != editor_id {
+        words.push(word);
+    }
+    words.join(" ")
+}
+
+/// Registers split_words() and fuzzy_score(), which the schema and the search queries use.
+/// Needed on every connection before `migration::migrate`.
+pub fn register_functions(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "split_words",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|s| split_words(&s))),
+    )?;
+    // NULL scores 0 so that MAX() over several columns still works
+    conn.create_scalar_function(
+        "fuzzy_score",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let query = ctx.get::<Option<String>>(0)?;
+            let text = ctx.get::<Option<String>>(1)?;
+            Ok(match (query, text) {
+                (Some(query), Some(text)) => fuzzy::score(&query, &text),
+                _ => 0.0,
+            })
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_editor_ids() {
+        assert_eq!(
+            "HousecarlWhiterun Housecarl Whiterun",
+            split_words("HousecarlWhiterun")
+        );
+        assert_eq!(
+            "dunGauldursonQST dun Gauldurson QST",
+            split_words("dunGauldursonQST")
+        );
+        assert_eq!("MS09 MS 09", split_words("MS09"));
+        assert_eq!("Lydia", split_words("Lydia"));
+        assert_eq!(
+            "Forbidden Legend Forbidden Legend",
+            split_words("Forbidden Legend")
+        );
+        assert_eq!("", split_words(""));
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::Context;
+use clap::{AppSettings, Arg, SubCommand};
+use skyrim_search_index::{migration, register_functions, replay};
+use std::io::BufReader;
+
+fn get_clap<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::App::new("skyrim-search-index")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("build skyrim-search-se databases outside the game")
+        .setting(AppSettings::DisableHelpSubcommand)
+        .setting(AppSettings::VersionlessSubcommands)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("apply recorded index events (record_events = true) to a database")
+                .arg(
+                    Arg::with_name("events")
+                        .help("recorded events (e.g. skyrim-search-se-events.log)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("db")
+                        .help("database to create or update")
+                        .required(true),
+                ),
+        )
+}
+
+fn replay_file(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let events = matches.value_of("events").unwrap();
+    let file = std::fs::File::open(events).with_context(|| format!("could not open {}", events))?;
+    let conn = rusqlite::Connection::open(matches.value_of("db").unwrap()).context("open")?;
+    conn.execute_batch("PRAGMA recursive_triggers=ON;")
+        .context("pragma")?;
+    register_functions(&conn).context("register_functions")?;
+    migration::migrate(&conn).context("migrate")?;
+
+    conn.execute_batch("BEGIN;").context("begin")?;
+    let applied = replay(&conn, BufReader::new(file))?;
+    conn.execute_batch("COMMIT;").context("commit")?;
+    println!("applied {} events", applied);
+    Ok(())
+}
+
+fn main() {
+    let matches = get_clap().get_matches();
+
+    let result = match matches.subcommand() {
+        ("replay", Some(matches)) => replay_file(matches),
+        _ => unreachable!(),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {:#}", err);
+        std::process::exit(2);
+    }
+}
@@ -0,0 +1,349 @@
+//! Schema of the search database. `PRAGMA user_version` is the number of steps in
+//! `MIGRATIONS` already applied; new steps are appended, never edited, so a persistent db
+//! built by an older version is brought forward instead of being misread.
+
+use anyhow::Context;
+use rusqlite::NO_PARAMS;
+
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema. Dbs from before versioning (user_version 0) are dropped and rebuilt.
+    r#"
+    DROP TABLE IF EXISTS npc_fts;
+    DROP TABLE IF EXISTS cell_fts;
+    DROP TABLE IF EXISTS quest_fts;
+    DROP TABLE IF EXISTS quest_stage_fts;
+    DROP TABLE IF EXISTS meta;
+    DROP TABLE IF EXISTS npc;
+    DROP TABLE IF EXISTS actor;
+    DROP TABLE IF EXISTS cell;
+    DROP TABLE IF EXISTS quest;
+    DROP TABLE IF EXISTS quest_stage;
+    DROP TABLE IF EXISTS history;
+    DROP TABLE IF EXISTS bookmark;
+
+    CREATE TABLE meta (
+        key TEXT PRIMARY KEY NOT NULL,
+        value
+    );
+
+    CREATE TABLE npc (
+        form_id INTEGER PRIMARY KEY NOT NULL,
+        editor_id TEXT COLLATE NOCASE,
+        name TEXT COLLATE NOCASE
+    );
+
+    CREATE TABLE actor (
+        form_id INTEGER PRIMARY KEY NOT NULL,
+        base_form_id INTEGER
+    );
+
+    CREATE TABLE cell (
+        form_id INTEGER PRIMARY KEY NOT NULL,
+        editor_id TEXT COLLATE NOCASE,
+        name TEXT COLLATE NOCASE
+    );
+
+    CREATE TABLE quest (
+        form_id INTEGER PRIMARY KEY NOT NULL,
+        editor_id TEXT COLLATE NOCASE,
+        name TEXT COLLATE NOCASE
+    );
+
+    CREATE TABLE quest_stage (
+        form_id TEXT COLLATE NOCASE,
+        stage INTEGER,
+        log INTEGER,
+        text TEXT,
+        PRIMARY KEY (form_id, stage)
+    );
+
+    CREATE VIRTUAL TABLE quest_stage_fts USING fts5(text);
+    CREATE TRIGGER quest_stage_fts_insert AFTER INSERT ON quest_stage BEGIN
+        INSERT INTO quest_stage_fts (rowid, text) VALUES (new.rowid, new.text);
+    END;
+    CREATE TRIGGER quest_stage_fts_update AFTER UPDATE ON quest_stage BEGIN
+        UPDATE quest_stage_fts SET text = new.text WHERE rowid = old.rowid;
+    END;
+    CREATE TRIGGER quest_stage_fts_delete AFTER DELETE ON quest_stage BEGIN
+        DELETE FROM quest_stage_fts WHERE rowid = old.rowid;
+    END;
+
+    CREATE TABLE history (
+        id INTEGER PRIMARY KEY NOT NULL,
+        command TEXT NOT NULL
+    );
+
+    CREATE TABLE bookmark (
+        form_id INTEGER PRIMARY KEY NOT NULL,
+        note TEXT
+    );
+
+    CREATE VIRTUAL TABLE npc_fts USING fts5(name, editor_id);
+    CREATE TRIGGER npc_fts_insert AFTER INSERT ON npc BEGIN
+        INSERT INTO npc_fts (rowid, name, editor_id)
+        VALUES (new.form_id, new.name, split_words(new.editor_id));
+    END;
+    CREATE TRIGGER npc_fts_update AFTER UPDATE ON npc BEGIN
+        UPDATE npc_fts SET name = new.name, editor_id = split_words(new.editor_id)
+        WHERE rowid = old.form_id;
+    END;
+    CREATE TRIGGER npc_fts_delete AFTER DELETE ON npc BEGIN
+        DELETE FROM npc_fts WHERE rowid = old.form_id;
+    END;
+
+    CREATE VIRTUAL TABLE cell_fts USING fts5(name, editor_id);
+    CREATE TRIGGER cell_fts_insert AFTER INSERT ON cell BEGIN
+        INSERT INTO cell_fts (rowid, name, editor_id)
+        VALUES (new.form_id, new.name, split_words(new.editor_id));
+    END;
+    CREATE TRIGGER cell_fts_update AFTER UPDATE ON cell BEGIN
+        UPDATE cell_fts SET name = new.name, editor_id = split_words(new.editor_id)
+        WHERE rowid = old.form_id;
+    END;
+    CREATE TRIGGER cell_fts_delete AFTER DELETE ON cell BEGIN
+        DELETE FROM cell_fts WHERE rowid = old.form_id;
+    END;
+
+    CREATE VIRTUAL TABLE quest_fts USING fts5(name, editor_id);
+    CREATE TRIGGER quest_fts_insert AFTER INSERT ON quest BEGIN
+        INSERT INTO quest_fts (rowid, name, editor_id)
+        VALUES (new.form_id, new.name, split_words(new.editor_id));
+    END;
+    CREATE TRIGGER quest_fts_update AFTER UPDATE ON quest BEGIN
+        UPDATE quest_fts SET name = new.name, editor_id = split_words(new.editor_id)
+        WHERE rowid = old.form_id;
+    END;
+    CREATE TRIGGER quest_fts_delete AFTER DELETE ON quest BEGIN
+        DELETE FROM quest_fts WHERE rowid = old.form_id;
+    END;
+    "#,
+    // 2: quest_stage.form_id was TEXT, so it only joined with quest.form_id through
+    // affinity conversion. Rowids are kept since quest_stage_fts refers to them.
+    r#"
+    CREATE TABLE quest_stage_v2 (
+        form_id INTEGER NOT NULL,
+        stage INTEGER NOT NULL,
+        log INTEGER,
+        text TEXT,
+        PRIMARY KEY (form_id, stage)
+    );
+    INSERT INTO quest_stage_v2 (rowid, form_id, stage, log, text)
+    SELECT rowid, CAST(form_id AS INTEGER), stage, log, text FROM quest_stage;
+    DROP TABLE quest_stage;
+    ALTER TABLE quest_stage_v2 RENAME TO quest_stage;
+
+    CREATE TRIGGER quest_stage_fts_insert AFTER INSERT ON quest_stage BEGIN
+        INSERT INTO quest_stage_fts (rowid, text) VALUES (new.rowid, new.text);
+    END;
+    CREATE TRIGGER quest_stage_fts_update AFTER UPDATE ON quest_stage BEGIN
+        UPDATE quest_stage_fts SET text = new.text WHERE rowid = old.rowid;
+    END;
+    CREATE TRIGGER quest_stage_fts_delete AFTER DELETE ON quest_stage BEGIN
+        DELETE FROM quest_stage_fts WHERE rowid = old.rowid;
+    END;
+    "#,
+    // 3: the plugin each form comes from. plugin_id is the load order index in the form id's
+    // top byte, or FExxx for light plugins, whose forms are FExxxyyy.
+    r#"
+    CREATE TABLE plugin (
+        id INTEGER PRIMARY KEY NOT NULL,
+        file_name TEXT NOT NULL COLLATE NOCASE
+    );
+
+    ALTER TABLE npc ADD COLUMN plugin_id INTEGER AS
+        (CASE form_id >> 24 WHEN 0xFE THEN form_id >> 12 ELSE form_id >> 24 END);
+    ALTER TABLE actor ADD COLUMN plugin_id INTEGER AS
+        (CASE form_id >> 24 WHEN 0xFE THEN form_id >> 12 ELSE form_id >> 24 END);
+    ALTER TABLE cell ADD COLUMN plugin_id INTEGER AS
+        (CASE form_id >> 24 WHEN 0xFE THEN form_id >> 12 ELSE form_id >> 24 END);
+    ALTER TABLE quest ADD COLUMN plugin_id INTEGER AS
+        (CASE form_id >> 24 WHEN 0xFE THEN form_id >> 12 ELSE form_id >> 24 END);
+    "#,
+];
+
+/// Version of the schema `migrate` produces.
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+pub fn user_version(conn: &rusqlite::Connection) -> rusqlite::Result<u32> {
+    conn.query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+}
+
+/// Applies every migration `conn` has not seen yet, each in its own transaction.
+/// The SQL functions of `register_functions` have to be registered first.
+pub fn migrate(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    migrate_to(conn, SCHEMA_VERSION)
+}
+
+fn migrate_to(conn: &rusqlite::Connection, target: u32) -> anyhow::Result<()> {
+    let version = user_version(conn).context("user_version")?;
+    if version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "schema version {} is newer than this plugin's {}",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+
+    for (i, sql) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .take(target as usize)
+        .skip(version as usize)
+    {
+        let version = i + 1;
+        conn.execute_batch(&format!(
+            "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+            sql, version
+        ))
+        .inspect_err(|_| {
+            conn.execute_batch("ROLLBACK;").ok();
+        })
+        .with_context(|| format!("migration {}", version))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::params;
+
+    fn open() -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA recursive_triggers=ON;").unwrap();
+        crate::register_functions(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn migrates_empty_db() {
+        let conn = open();
+        migrate(&conn).unwrap();
+        assert_eq!(SCHEMA_VERSION, user_version(&conn).unwrap());
+
+        // running again is a no-op
+        migrate(&conn).unwrap();
+        assert_eq!(SCHEMA_VERSION, user_version(&conn).unwrap());
+    }
+
+    #[test]
+    fn rebuilds_unversioned_db() {
+        let conn = open();
+        conn.execute_batch(
+            "CREATE TABLE npc (form_id INTEGER PRIMARY KEY, editor_id TEXT, name TEXT); \
+             INSERT INTO npc VALUES (0xA2C8E, 'HousecarlWhiterun', 'Lydia');",
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM npc", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(0, count);
+    }
+
+    #[test]
+    fn migrates_v1_db() {
+        let conn = open();
+        migrate_to(&conn, 1).unwrap();
+        conn.execute_batch(include_str!("../tests/fixtures/v1.sql"))
+            .unwrap();
+        assert_eq!(1, user_version(&conn).unwrap());
+
+        migrate(&conn).unwrap();
+        assert_eq!(SCHEMA_VERSION, user_version(&conn).unwrap());
+
+        let form_id_type: String = conn
+            .query_row(
+                "SELECT typeof(form_id) FROM quest_stage LIMIT 1",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!("integer", form_id_type);
+
+        // the quest_stage join and its full text index survive the table rebuild
+        let stages: Vec<(i64, i64)> = conn
+            .prepare(
+                "SELECT quest.form_id, stage FROM quest \
+                 JOIN quest_stage ON quest.form_id = quest_stage.form_id \
+                 JOIN quest_stage_fts ON quest_stage_fts.rowid = quest_stage.rowid \
+                 WHERE quest_stage_fts MATCH 'brothers'",
+            )
+            .unwrap()
+            .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(vec![(0xE4D31, 150)], stages);
+
+        // triggers were recreated on the new table
+        conn.execute(
+            "INSERT INTO quest_stage (form_id, stage, log, text) VALUES (?, ?, ?, ?)",
+            params![0xE4D31, 200, 7, "I restored the amulet"],
+        )
+        .unwrap();
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM quest_stage_fts WHERE quest_stage_fts MATCH 'amulet'",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(1, count);
+
+        let name: String = conn
+            .query_row(
+                "SELECT npc.name FROM npc JOIN npc_fts ON npc.form_id = npc_fts.rowid \
+                 WHERE npc_fts MATCH 'whiterun'",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!("Lydia", name);
+    }
+
+    #[test]
+    fn derives_plugin_ids() {
+        let conn = open();
+        migrate(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO plugin VALUES (0x02, 'Dawnguard.esm'), (0xFE001, 'ccBGSSSE001-Fish.esm'); \
+             INSERT INTO npc (form_id, name) VALUES \
+             (0x02003B8B, 'Serana'), (0xFE001805, 'Fishing Guide'), (0xFF000D66, 'Clone');",
+        )
+        .unwrap();
+
+        let plugins: Vec<(String, Option<String>)> = conn
+            .prepare(
+                "SELECT npc.name, plugin.file_name FROM npc \
+                 LEFT JOIN plugin ON plugin.id = npc.plugin_id ORDER BY npc.form_id",
+            )
+            .unwrap()
+            .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            vec![
+                (String::from("Serana"), Some(String::from("Dawnguard.esm"))),
+                (
+                    String::from("Fishing Guide"),
+                    Some(String::from("ccBGSSSE001-Fish.esm"))
+                ),
+                // created at runtime
+                (String::from("Clone"), None),
+            ],
+            plugins
+        );
+    }
+
+    #[test]
+    fn refuses_newer_db() {
+        let conn = open();
+        conn.execute_batch(&format!("PRAGMA user_version = {};", SCHEMA_VERSION + 1))
+            .unwrap();
+        assert!(migrate(&conn).is_err());
+    }
+}
@@ -0,0 +1,422 @@
+//! The search queries behind the console commands, run against a db migrated by
+//! `migration::migrate` with the functions of `register_functions`.
+//!
+//! ?1 is the FTS5 query from `fts_query`, ?2 the form id if the query parses as hex and ?3 the
+//! plugin file name to filter by, or NULL for all plugins.
+//! Exact form id matches come first, then full text matches ranked by bm25.
+
+macro_rules! plugin_join {
+    ($table:literal) => {
+        concat!(" LEFT JOIN plugin ON plugin.id = ", $table, ".plugin_id ")
+    };
+}
+
+macro_rules! plugin_filter {
+    () => {
+        " (?3 IS NULL OR plugin.file_name = ?3) "
+    };
+}
+
+macro_rules! npc_columns {
+    () => {
+        "npc.form_id, npc.editor_id, npc.name, plugin.file_name AS plugin, \
+         actor.form_id AS ref_id"
+    };
+}
+
+// cell and quest have the same columns
+macro_rules! form_columns {
+    ($table:literal) => {
+        concat!(
+            $table,
+            ".form_id, ",
+            $table,
+            ".editor_id, ",
+            $table,
+            ".name, plugin.file_name AS plugin"
+        )
+    };
+}
+
+macro_rules! npc_fts_sql {
+    () => {
+        concat!(
+            "SELECT ",
+            npc_columns!(),
+            " FROM npc_fts \
+             JOIN npc ON npc.form_id = npc_fts.rowid",
+            plugin_join!("npc"),
+            "LEFT JOIN actor ON npc.form_id = actor.base_form_id \
+             WHERE npc_fts MATCH ?1 AND",
+            plugin_filter!(),
+            "ORDER BY bm25(npc_fts)"
+        )
+    };
+}
+
+macro_rules! form_fts_sql {
+    ($table:literal) => {
+        concat!(
+            "SELECT ",
+            form_columns!($table),
+            " FROM ",
+            $table,
+            "_fts \
+             JOIN ",
+            $table,
+            " ON ",
+            $table,
+            ".form_id = ",
+            $table,
+            "_fts.rowid",
+            plugin_join!($table),
+            "WHERE ",
+            $table,
+            "_fts MATCH ?1 AND",
+            plugin_filter!(),
+            "ORDER BY bm25(",
+            $table,
+            "_fts)"
+        )
+    };
+}
+
+macro_rules! form_by_id_sql {
+    ($table:literal) => {
+        concat!(
+            "SELECT ",
+            form_columns!($table),
+            " FROM ",
+            $table,
+            plugin_join!($table),
+            "WHERE ",
+            $table,
+            ".form_id=?2 AND",
+            plugin_filter!(),
+            "UNION ALL SELECT * FROM (",
+            form_fts_sql!($table),
+            ") WHERE form_id != ?2"
+        )
+    };
+}
+
+// Rows matched only by form id have no rank, so they get their own sort key to come first.
+macro_rules! quest_stage_sql {
+    ($extra_condition:literal, $first_order:literal) => {
+        concat!(
+            "SELECT ",
+            form_columns!("quest"),
+            ", stage, log FROM quest \
+             JOIN quest_stage ON quest.form_id = quest_stage.form_id",
+            plugin_join!("quest"),
+            "LEFT JOIN (SELECT rowid, bm25(quest_fts) AS rank FROM quest_fts \
+             WHERE quest_fts MATCH ?1) AS quest_match ON quest_match.rowid = quest.form_id \
+             LEFT JOIN (SELECT rowid, bm25(quest_stage_fts) AS rank FROM quest_stage_fts \
+             WHERE quest_stage_fts MATCH ?1) AS stage_match ON stage_match.rowid = quest_stage.rowid \
+             WHERE log IS NOT NULL AND",
+            plugin_filter!(),
+            "AND (quest_match.rowid IS NOT NULL \
+             OR stage_match.rowid IS NOT NULL",
+            $extra_condition,
+            ") ORDER BY ",
+            $first_order,
+            "MIN(IFNULL(quest_match.rank, 0), IFNULL(stage_match.rank, 0)), quest.form_id, stage"
+        )
+    };
+}
+
+pub const NPC_SQL: &str = npc_fts_sql!();
+pub const NPC_BY_ID_SQL: &str = concat!(
+    "SELECT ",
+    npc_columns!(),
+    " FROM npc",
+    plugin_join!("npc"),
+    "LEFT JOIN actor ON npc.form_id = actor.base_form_id \
+     WHERE (npc.form_id=?2 OR actor.form_id=?2) AND",
+    plugin_filter!(),
+    "UNION ALL SELECT * FROM (",
+    npc_fts_sql!(),
+    ") WHERE form_id != ?2 AND ref_id IS NOT ?2"
+);
+
+pub const CELL_SQL: &str = form_fts_sql!("cell");
+pub const CELL_BY_ID_SQL: &str = form_by_id_sql!("cell");
+
+pub const QUEST_SQL: &str = form_fts_sql!("quest");
+pub const QUEST_BY_ID_SQL: &str = form_by_id_sql!("quest");
+
+pub const QUEST_STAGE_SQL: &str = quest_stage_sql!("", "");
+pub const QUEST_STAGE_BY_ID_SQL: &str =
+    quest_stage_sql!(" OR quest.form_id=?2", "quest.form_id=?2 DESC, ");
+
+// Fallback when full text search finds nothing: the closest names/editor ids by
+// fuzzy_score(?1, ...), best first, with ?2 the minimum score and ?3 the plugin as above.
+// Scores come last except for quest stages, whose log column has to stay last for the
+// journal lookup.
+
+macro_rules! fuzzy_subquery {
+    ($table:literal) => {
+        concat!(
+            "(SELECT form_id, MAX(fuzzy_score(?1, name), fuzzy_score(?1, editor_id)) AS score \
+             FROM ",
+            $table,
+            ") AS fuzzy"
+        )
+    };
+}
+
+macro_rules! form_fuzzy_sql {
+    ($table:literal) => {
+        concat!(
+            "SELECT ",
+            form_columns!($table),
+            ", ROUND(fuzzy.score, 2) AS score FROM ",
+            fuzzy_subquery!($table),
+            " JOIN ",
+            $table,
+            " ON ",
+            $table,
+            ".form_id = fuzzy.form_id",
+            plugin_join!($table),
+            "WHERE fuzzy.score >= ?2 AND",
+            plugin_filter!(),
+            "ORDER BY fuzzy.score DESC LIMIT 20"
+        )
+    };
+}
+
+pub const NPC_FUZZY_SQL: &str = concat!(
+    "SELECT ",
+    npc_columns!(),
+    ", ROUND(fuzzy.score, 2) AS score FROM ",
+    fuzzy_subquery!("npc"),
+    " JOIN npc ON npc.form_id = fuzzy.form_id",
+    plugin_join!("npc"),
+    "LEFT JOIN actor ON npc.form_id = actor.base_form_id \
+     WHERE fuzzy.score >= ?2 AND",
+    plugin_filter!(),
+    "ORDER BY fuzzy.score DESC LIMIT 20"
+);
+pub const CELL_FUZZY_SQL: &str = form_fuzzy_sql!("cell");
+pub const QUEST_FUZZY_SQL: &str = form_fuzzy_sql!("quest");
+pub const QUEST_STAGE_FUZZY_SQL: &str = concat!(
+    "SELECT ",
+    form_columns!("quest"),
+    ", stage, ROUND(fuzzy.score, 2) AS score, log FROM ",
+    fuzzy_subquery!("quest"),
+    " JOIN quest ON quest.form_id = fuzzy.form_id \
+     JOIN quest_stage ON quest.form_id = quest_stage.form_id",
+    plugin_join!("quest"),
+    "WHERE log IS NOT NULL AND fuzzy.score >= ?2 AND",
+    plugin_filter!(),
+    "ORDER BY fuzzy.score DESC, quest.form_id, stage LIMIT 50"
+);
+
+/// "forbidden leg" -> `"forbidden"* "leg"*`: every word has to match, each as a prefix.
+/// None if there is no word to search for.
+pub fn fts_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{migration, IndexEvent, Indexer};
+    use rusqlite::params;
+    use rusqlite::types::Null;
+
+    const LYDIA: u32 = 0xA2C8E;
+    const LYDIA_REF: u32 = 0xA2C94;
+    const IONA: u32 = 0xA2C91;
+    const BREEZEHOME: u32 = 0x165A8;
+    const DRAGONSREACH: u32 = 0x1A26F;
+    const GAULDUR: u32 = 0xE4D31;
+    const PARTY: u32 = 0x2A4B1;
+
+    fn open() -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA recursive_triggers=ON;").unwrap();
+        crate::register_functions(&conn).unwrap();
+        migration::migrate(&conn).unwrap();
+
+        let some = |s: &str| Some(String::from(s));
+        let indexer = Indexer::new(&conn);
+        for event in [
+            IndexEvent::Plugin {
+                id: 0,
+                file_name: String::from("Skyrim.esm"),
+            },
+            IndexEvent::NpcEditorId {
+                form_id: LYDIA,
+                editor_id: String::from("HousecarlWhiterun"),
+            },
+            IndexEvent::NpcName {
+                form_id: LYDIA,
+                name: String::from("Lydia"),
+            },
+            IndexEvent::ActorRef {
+                form_id: LYDIA_REF,
+                base_form_id: LYDIA,
+            },
+            IndexEvent::NpcEditorId {
+                form_id: IONA,
+                editor_id: String::from("HousecarlRiften"),
+            },
+            IndexEvent::Cell {
+                form_id: BREEZEHOME,
+                editor_id: some("WhiterunBreezehome"),
+                name: some("Breezehome"),
+            },
+            IndexEvent::Cell {
+                form_id: DRAGONSREACH,
+                editor_id: some("WhiterunDragonsreach"),
+                name: some("Dragonsreach"),
+            },
+            IndexEvent::Quest {
+                form_id: GAULDUR,
+                editor_id: some("dunGauldursonQST"),
+                name: some("Forbidden Legend"),
+            },
+            IndexEvent::Quest {
+                form_id: PARTY,
+                editor_id: some("MS09"),
+                name: some("The Legend of the Thalmor Embassy and the Dragon Crossing"),
+            },
+            IndexEvent::QuestStage {
+                form_id: GAULDUR,
+                stage: 30,
+                log: 5,
+            },
+            IndexEvent::QuestStage {
+                form_id: PARTY,
+                stage: 10,
+                log: 1,
+            },
+        ] {
+            indexer.apply(&event).unwrap();
+        }
+        conn.execute(
+            "UPDATE quest_stage SET text = 'I should find the Gauldur amulet fragments.' \
+             WHERE form_id = ?",
+            params![GAULDUR],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE quest_stage SET text = 'Delphine asked me to get into the embassy.' \
+             WHERE form_id = ?",
+            params![PARTY],
+        )
+        .unwrap();
+        conn
+    }
+
+    // form ids of the rows `sql` returns for the console query `query`
+    fn search(
+        conn: &rusqlite::Connection,
+        sql: &str,
+        query: &str,
+        id: Option<u32>,
+        plugin: Option<&str>,
+    ) -> Vec<u32> {
+        let fts_query = fts_query(query).unwrap();
+        conn.prepare(sql)
+            .unwrap()
+            .query_map(params![fts_query, id, plugin], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn fts_queries() {
+        assert_eq!(
+            Some(String::from(r#""forbidden"* "leg"*"#)),
+            fts_query("forbidden leg")
+        );
+        // FTS5 syntax is not passed through
+        assert_eq!(
+            Some(String::from(r#""lydia"* "NEAR"* "iona"*"#)),
+            fts_query("\"lydia\" NEAR(iona)")
+        );
+        assert_eq!(None, fts_query(" - "));
+    }
+
+    #[test]
+    fn full_text() {
+        let conn = open();
+        assert_eq!(vec![LYDIA], search(&conn, NPC_SQL, "lyd", None, None));
+        // words of the editor id
+        assert_eq!(
+            vec![LYDIA],
+            search(&conn, NPC_SQL, "housecarl white", None, None)
+        );
+        assert_eq!(
+            vec![BREEZEHOME],
+            search(&conn, CELL_SQL, "breeze", None, None)
+        );
+        assert_eq!(
+            vec![BREEZEHOME],
+            search(&conn, CELL_SQL, "breeze", None, Some("skyrim.esm"))
+        );
+        assert!(search(&conn, CELL_SQL, "breeze", None, Some("Dawnguard.esm")).is_empty());
+
+        let ref_id: u32 = conn
+            .query_row(
+                NPC_SQL,
+                params![fts_query("lydia").unwrap(), Null, Null],
+                |row| row.get("ref_id"),
+            )
+            .unwrap();
+        assert_eq!(LYDIA_REF, ref_id);
+    }
+
+    #[test]
+    fn ranked_by_bm25() {
+        let conn = open();
+        // the shorter name matches better
+        assert_eq!(
+            vec![GAULDUR, PARTY],
+            search(&conn, QUEST_SQL, "legend", None, None)
+        );
+        // quest names and stage texts
+        assert_eq!(
+            vec![GAULDUR],
+            search(&conn, QUEST_STAGE_SQL, "amulet", None, None)
+        );
+        assert_eq!(
+            vec![GAULDUR, PARTY],
+            search(&conn, QUEST_STAGE_SQL, "legend", None, None)
+        );
+    }
+
+    #[test]
+    fn form_id_matches_first() {
+        let conn = open();
+        assert_eq!(
+            vec![PARTY, GAULDUR],
+            search(&conn, QUEST_BY_ID_SQL, "legend", Some(PARTY), None)
+        );
+        // PARTY only matches by form id
+        assert_eq!(
+            vec![PARTY, GAULDUR],
+            search(&conn, QUEST_STAGE_BY_ID_SQL, "amulet", Some(PARTY), None)
+        );
+        assert_eq!(
+            vec![BREEZEHOME, DRAGONSREACH],
+            search(&conn, CELL_BY_ID_SQL, "dragon", Some(BREEZEHOME), None)
+        );
+        // the base npc of a reference
+        assert_eq!(
+            vec![LYDIA, IONA],
+            search(&conn, NPC_BY_ID_SQL, "housecarl", Some(LYDIA_REF), None)
+        );
+    }
+}
@@ -0,0 +1,9 @@
+-- Rows as written by schema version 1, where quest_stage.form_id was TEXT
+INSERT INTO npc (form_id, editor_id, name) VALUES (666766, 'HousecarlWhiterun', 'Lydia');
+INSERT INTO actor (form_id, base_form_id) VALUES (666772, 666766);
+INSERT INTO cell (form_id, editor_id, name) VALUES (91560, 'WhiterunBreezehome', 'Breezehome');
+INSERT INTO quest (form_id, editor_id, name) VALUES (937265, 'dunGauldursonQST', 'Forbidden Legend');
+INSERT INTO quest_stage (form_id, stage, log, text) VALUES (937265, 10, 1, NULL);
+INSERT INTO quest_stage (form_id, stage, log, text) VALUES (937265, 30, 3, 'Long ago, the Archmage Gauldur was murdered by his three sons.');
+INSERT INTO quest_stage (form_id, stage, log, text) VALUES (937265, 150, 6, 'I defeated the undead brothers.');
+INSERT INTO meta (key, value) VALUES ('complete', 1);
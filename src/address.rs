@@ -0,0 +1,129 @@
+use crate::console;
+use anyhow::{anyhow, Context};
+use late_static::LateStatic;
+use std::fmt::Formatter;
+use std::ops::Deref;
+use versionlib::CompactVersionlibData;
+use win_dbg_logger::output_debug_string;
+
+// Address Library for SKSE Plugins installs its databases here.
+const VERSIONLIB_DIR: &str = "Data\\SKSE\\Plugins";
+
+const MODULE_NAME: &str = "SkyrimSE.exe";
+// Console_ProcessInput (441582) is only in the databases from 1.6.1130 on.
+// Keep in sync with MIN_VERSION in versionlib-macro.
+const MIN_RUNTIME_VERSION: [u32; 4] = [1, 6, 1130, 0];
+
+struct State {
+    path: String,
+    data: CompactVersionlibData,
+}
+static S: LateStatic<State> = LateStatic::new();
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("path", &self.path)
+            .field("version", &self.data.version)
+            .field("num_addresses", &self.data.len())
+            .finish()
+    }
+}
+
+// Inverse of SKSE's MAKE_EXE_VERSION_EX
+pub(crate) fn unpack_version(packed: u32) -> [u32; 4] {
+    [
+        (packed >> 24) & 0xff,
+        (packed >> 16) & 0xff,
+        (packed >> 4) & 0xfff,
+        packed & 0xf,
+    ]
+}
+
+pub(crate) fn versionlib_path(version: [u32; 4]) -> String {
+    format!("{}\\{}", VERSIONLIB_DIR, versionlib::file_name(version))
+}
+
+pub(crate) fn resolve(address_id: u64) -> anyhow::Result<usize> {
+    let offset = S.data.get(address_id).ok_or_else(|| {
+        anyhow!(
+            "address id {} is not in {} ({:?})",
+            address_id,
+            S.path,
+            S.data.version
+        )
+    })?;
+    Ok(offset as usize)
+}
+
+fn version_string(version: [u32; 4]) -> String {
+    version.map(|v| v.to_string()).join(".")
+}
+
+// Refuses databases that would make us patch the wrong addresses.
+fn check_compatibility(
+    runtime_version: [u32; 4],
+    path: &str,
+    data: &CompactVersionlibData,
+) -> anyhow::Result<()> {
+    if data.version != runtime_version {
+        anyhow::bail!(
+            "{} is for Skyrim {} but the game is {}; reinstall Address Library for SKSE Plugins",
+            path,
+            version_string(data.version),
+            version_string(runtime_version)
+        );
+    }
+    let module_name = String::from_utf8_lossy(&data.module_name_raw);
+    if !module_name.eq_ignore_ascii_case(MODULE_NAME) {
+        anyhow::bail!(
+            "{} is for {} instead of {}; install the Address Library for Skyrim Special Edition",
+            path,
+            module_name,
+            MODULE_NAME
+        );
+    }
+    Ok(())
+}
+
+unsafe fn load(runtime_version: [u32; 4]) -> anyhow::Result<()> {
+    // not every id in addresses.toml exists before MIN_RUNTIME_VERSION
+    if runtime_version < MIN_RUNTIME_VERSION {
+        anyhow::bail!(
+            "Skyrim {} is not supported; this build of Skyrim Search SE needs Skyrim {} or later \
+             (built against {})",
+            version_string(runtime_version),
+            version_string(MIN_RUNTIME_VERSION),
+            version_string(unpack_version(target_version!()))
+        );
+    }
+
+    let path = versionlib_path(runtime_version);
+    let data = versionlib::open_compact(&path).with_context(|| {
+        format!(
+            "could not load {}; install Address Library for SKSE Plugins for Skyrim {}",
+            path,
+            version_string(runtime_version)
+        )
+    })?;
+    check_compatibility(runtime_version, &path, &data)?;
+
+    LateStatic::assign(&S, State { path, data });
+
+    output_debug_string(format!("S: {:#?}", S.deref()).as_str());
+
+    Ok(())
+}
+
+/// Nothing may be patched unless this succeeded; on failure the reason is also queued for
+/// the console since the log file is easy to miss.
+pub(crate) unsafe fn init(runtime_version: u32) -> anyhow::Result<()> {
+    let result = load(unpack_version(runtime_version));
+    if let Err(err) = &result {
+        console::queue(format!(
+            "Skyrim Search SE is disabled and did not patch the game: {:#}",
+            err
+        ));
+    }
+    result
+}
@@ -0,0 +1,196 @@
+//! C API for other SKSE plugins; see include/skyrim_search.h. Keep both in sync and bump
+//! `API_VERSION` whenever a signature changes.
+
+use crate::db;
+use crate::log::Loggable;
+use crate::messaging;
+use anyhow::Context;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, OptionalExtension, NO_PARAMS};
+use std::ffi::{CStr, CString};
+use std::ptr;
+use winapi::ctypes::{c_char, c_void};
+
+pub const API_VERSION: u32 = 1;
+
+/// Message type dispatched to listeners of "Skyrim Search SE" once the API is usable.
+/// `data` points to an `Api`.
+pub const MESSAGE_API_READY: u32 = 0x53534530; // 'SSE0'
+
+pub const RESULT_OK: i32 = 0;
+pub const RESULT_INVALID_ARGUMENT: i32 = -1;
+pub const RESULT_NOT_FOUND: i32 = -2;
+pub const RESULT_QUERY_FAILED: i32 = -3;
+
+/// Called once per row; return false to stop. Pointers are only valid during the call and
+/// NULL columns are passed as NULL.
+pub type RowCallback = extern "C" fn(
+    userdata: *mut c_void,
+    column_count: u32,
+    names: *const *const c_char,
+    values: *const *const c_char,
+) -> bool;
+
+#[allow(dead_code)]
+#[repr(C)]
+pub struct Api {
+    version: u32,
+    query: extern "C" fn(*const c_char, RowCallback, *mut c_void) -> i32,
+    lookup_name: extern "C" fn(u32, *mut c_char, u32) -> i32,
+    lookup_editor_id: extern "C" fn(u32, *mut c_char, u32) -> i32,
+}
+
+static API: Api = Api {
+    version: API_VERSION,
+    query: SkyrimSearch_Query,
+    lookup_name: SkyrimSearch_LookupName,
+    lookup_editor_id: SkyrimSearch_LookupEditorId,
+};
+
+fn column_text(column: ValueRef) -> Option<CString> {
+    let text = match column {
+        ValueRef::Null => return None,
+        ValueRef::Integer(v) => v.to_string(),
+        ValueRef::Real(v) => v.to_string(),
+        ValueRef::Text(v) => String::from_utf8_lossy(v).to_string(),
+        ValueRef::Blob(v) => format!("<{}-byte blob>", v.len()),
+    };
+    // interior NULs cannot be represented in a C string
+    CString::new(text.replace('\0', "")).ok()
+}
+
+fn query(sql: &str, callback: RowCallback, userdata: *mut c_void) -> anyhow::Result<()> {
+    // Rows are collected first so that the callback may call back into this API; `READER`
+    // is not reentrant.
+    let (names, rows) = {
+        // callers only get to read; the index is written by the worker alone
+        let db = db::READER.lock().unwrap();
+        let mut stmt = db.prepare(sql).context("prepare error")?;
+        let names: Vec<CString> = stmt
+            .column_names()
+            .into_iter()
+            .map(|name| CString::new(name).unwrap_or_default())
+            .collect();
+        let mut rows = Vec::new();
+        let mut query = stmt.query(NO_PARAMS).context("query error")?;
+        while let Some(row) = query.next().context("rows.next() error")? {
+            let values: Vec<Option<CString>> = (0..names.len())
+                .map(|i| column_text(row.get_raw(i)))
+                .collect();
+            rows.push(values);
+        }
+        (names, rows)
+    };
+
+    let name_ptrs: Vec<*const c_char> = names.iter().map(|name| name.as_ptr()).collect();
+    for values in rows.iter() {
+        let value_ptrs: Vec<*const c_char> = values
+            .iter()
+            .map(|value| value.as_ref().map_or(ptr::null(), |value| value.as_ptr()))
+            .collect();
+        if !callback(
+            userdata,
+            name_ptrs.len() as u32,
+            name_ptrs.as_ptr(),
+            value_ptrs.as_ptr(),
+        ) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Runs a read-only SQL query against the search index.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn SkyrimSearch_Query(
+    sql: *const c_char,
+    callback: RowCallback,
+    userdata: *mut c_void,
+) -> i32 {
+    if sql.is_null() {
+        return RESULT_INVALID_ARGUMENT;
+    }
+    let sql = match unsafe { CStr::from_ptr(sql) }.to_str() {
+        Ok(sql) => sql,
+        Err(_) => return RESULT_INVALID_ARGUMENT,
+    };
+    match query(sql, callback, userdata)
+        .context("SkyrimSearch_Query")
+        .logging_ok()
+    {
+        Some(()) => RESULT_OK,
+        None => RESULT_QUERY_FAILED,
+    }
+}
+
+// `column` of the npc, cell or quest with `form_id`; refs resolve to their base npc.
+fn lookup(column: &str, form_id: u32) -> anyhow::Result<Option<String>> {
+    let db = db::READER.lock().unwrap();
+    let sql = format!(
+        "SELECT {0} FROM npc WHERE form_id = COALESCE( \
+         (SELECT base_form_id FROM actor WHERE form_id = ?1), ?1) AND {0} IS NOT NULL \
+         UNION ALL SELECT {0} FROM cell WHERE form_id = ?1 AND {0} IS NOT NULL \
+         UNION ALL SELECT {0} FROM quest WHERE form_id = ?1 AND {0} IS NOT NULL \
+         LIMIT 1",
+        column
+    );
+    let value = db
+        .prepare_cached(sql.as_str())
+        .context("prepare error")?
+        .query_row(params![form_id], |row| row.get(0))
+        .optional()
+        .context("query error")?;
+    Ok(value)
+}
+
+// Copies as much of `value` as fits into `buf`, always NUL terminated, and returns its full
+// length so callers can retry with a bigger buffer.
+fn copy_to_buffer(value: &str, buf: *mut c_char, len: u32) -> i32 {
+    if !buf.is_null() && len > 0 {
+        let n = value.len().min(len as usize - 1);
+        unsafe {
+            ptr::copy_nonoverlapping(value.as_ptr() as *const c_char, buf, n);
+            *buf.add(n) = 0;
+        }
+    }
+    value.len() as i32
+}
+
+fn lookup_into(column: &str, form_id: u32, buf: *mut c_char, len: u32) -> i32 {
+    match lookup(column, form_id)
+        .context("SkyrimSearch lookup")
+        .logging_ok()
+    {
+        Some(Some(value)) => copy_to_buffer(&value, buf, len),
+        Some(None) => RESULT_NOT_FOUND,
+        None => RESULT_QUERY_FAILED,
+    }
+}
+
+/// Looks up the display name of a form or reference.
+#[no_mangle]
+pub extern "C" fn SkyrimSearch_LookupName(form_id: u32, buf: *mut c_char, len: u32) -> i32 {
+    lookup_into("name", form_id, buf, len)
+}
+
+/// Looks up the editor id of a form or reference, which the game itself does not keep.
+#[no_mangle]
+pub extern "C" fn SkyrimSearch_LookupEditorId(form_id: u32, buf: *mut c_char, len: u32) -> i32 {
+    lookup_into("editor_id", form_id, buf, len)
+}
+
+#[no_mangle]
+pub extern "C" fn SkyrimSearch_GetApiVersion() -> u32 {
+    API_VERSION
+}
+
+/// Tells listening plugins where the API is; called on kPostPostLoad, after every plugin had the
+/// chance to register a listener in its kPostLoad handler.
+pub(crate) fn announce() -> anyhow::Result<()> {
+    messaging::dispatch(
+        MESSAGE_API_READY,
+        &API as *const Api as *mut c_void,
+        std::mem::size_of::<Api>() as u32,
+    )
+}
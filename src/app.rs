@@ -1,16 +1,14 @@
-use crate::db::Job;
-use crate::form::qust::TESQuest;
-use crate::form::TESForm;
+use crate::db::TaskQueue;
+use crate::form;
 use crate::log::Loggable;
-use crate::{console, db};
+use crate::{console, db, messaging, search, status};
 use anyhow::{anyhow, Context};
 use clap::{AppSettings, Arg, SubCommand};
 use late_static::LateStatic;
 use rusqlite::params;
-use rusqlite::types::ValueRef;
+use rusqlite::types::{Null, ValueRef};
 use rusqlite::{Statement, NO_PARAMS};
-use std::sync::mpsc::Sender;
-use std::sync::{Arc, Condvar, Mutex};
+use skyrim_search_index::fuzzy;
 
 pub(crate) enum ProcessResult {
     Processed,
@@ -48,6 +46,17 @@ pub fn get_clap<'a, 'b>() -> clap::App<'a, 'b> {
                         .help("search query (e.g. name, edid, form_id, ref_id)")
                         .required(true)
                         .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("fuzzy")
+                        .long("fuzzy")
+                        .help("show the closest matches even if there are exact ones"),
+                )
+                .arg(
+                    Arg::with_name("plugin")
+                        .long("plugin")
+                        .takes_value(true)
+                        .help("only show forms from this plugin (e.g. Dawnguard.esm)"),
                 ),
         )
         .subcommand(
@@ -59,6 +68,17 @@ pub fn get_clap<'a, 'b>() -> clap::App<'a, 'b> {
                         .help("search query (e.g. name, edid, form_id)")
                         .required(true)
                         .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("fuzzy")
+                        .long("fuzzy")
+                        .help("show the closest matches even if there are exact ones"),
+                )
+                .arg(
+                    Arg::with_name("plugin")
+                        .long("plugin")
+                        .takes_value(true)
+                        .help("only show forms from this plugin (e.g. Dawnguard.esm)"),
                 ),
         )
         .subcommand(
@@ -70,6 +90,17 @@ pub fn get_clap<'a, 'b>() -> clap::App<'a, 'b> {
                         .help("search query (e.g. name, edid, form_id)")
                         .required(true)
                         .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("fuzzy")
+                        .long("fuzzy")
+                        .help("show the closest matches even if there are exact ones"),
+                )
+                .arg(
+                    Arg::with_name("plugin")
+                        .long("plugin")
+                        .takes_value(true)
+                        .help("only show forms from this plugin (e.g. Dawnguard.esm)"),
                 ),
         )
         .subcommand(
@@ -84,12 +115,47 @@ pub fn get_clap<'a, 'b>() -> clap::App<'a, 'b> {
                         .help("search query (e.g. name, edid, form_id)")
                         .required(true)
                         .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("fuzzy")
+                        .long("fuzzy")
+                        .help("show the closest matches even if there are exact ones"),
+                )
+                .arg(
+                    Arg::with_name("plugin")
+                        .long("plugin")
+                        .takes_value(true)
+                        .help("only show forms from this plugin (e.g. Dawnguard.esm)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("status").about("show what the search index was built for"),
+        )
+        .subcommand(
+            SubCommand::with_name("worker").about("show how the indexing worker keeps up"),
+        )
+        .subcommand(SubCommand::with_name("history").about("show ss commands run in this save"))
+        .subcommand(
+            SubCommand::with_name("bookmark")
+                .alias("bookmarks")
+                .about("bookmark a form/reference in this save, or list bookmarks")
+                .arg(Arg::with_name("form_id").help("form_id or ref_id to bookmark"))
+                .arg(
+                    Arg::with_name("remove")
+                        .long("remove")
+                        .help("remove the bookmark instead")
+                        .requires("form_id"),
+                )
+                .arg(
+                    Arg::with_name("note")
+                        .help("note to show with the bookmark")
+                        .multiple(true),
                 ),
         )
 }
 
 struct State {
-    task_queue: Sender<Job>,
+    task_queue: TaskQueue,
 }
 unsafe impl Sync for State {}
 static S: LateStatic<State> = LateStatic::new();
@@ -118,6 +184,7 @@ pub(crate) fn process_console_input(input: &str) -> anyhow::Result<ProcessResult
         };
     }
 
+    let command_line = input.join(" ");
     let matches = get_clap().get_matches_from_safe(input)?;
 
     if matches.is_present("debug") {
@@ -126,26 +193,15 @@ pub(crate) fn process_console_input(input: &str) -> anyhow::Result<ProcessResult
 
     static CREATE_INDEX: std::sync::Once = std::sync::Once::new();
     CREATE_INDEX.call_once(|| {
-        let pair = Arc::new((Mutex::new(()), Condvar::new()));
-        let pair2 = Arc::clone(&pair);
+        // queries until then see what is indexed so far, see print_index_progress
         S.task_queue
-            .send(Box::new(move |db| {
-                db::init_index(db).logging_ok();
-
-                pair2.1.notify_one();
-
-                Ok(())
-            }))
+            .send(Box::new(|db| db::init_index(db).context("init_index")))
             .map_err(|e| anyhow!(e.to_string()))
             .logging_ok();
-        let (lock, cond) = &*pair;
-        if let Ok(guard) = lock.lock() {
-            cond.wait(guard)
-                .map_err(|e| anyhow!(e.to_string()))
-                .logging_ok();
-        };
     });
 
+    record_history(command_line).logging_ok();
+
     if let Some(matches) = matches.subcommand_matches("raw") {
         process_raw_query_command(matches)?;
     } else if let Some(matches) = matches.subcommand_matches("npc") {
@@ -156,243 +212,322 @@ pub(crate) fn process_console_input(input: &str) -> anyhow::Result<ProcessResult
         process_quest_command(matches)?;
     } else if let Some(matches) = matches.subcommand_matches("quest_stage") {
         process_quest_log_command(matches)?;
+    } else if matches.subcommand_matches("status").is_some() {
+        process_status_command()?;
+    } else if matches.subcommand_matches("worker").is_some() {
+        process_worker_command()?;
+    } else if matches.subcommand_matches("history").is_some() {
+        process_history_command()?;
+    } else if let Some(matches) = matches.subcommand_matches("bookmark") {
+        process_bookmark_command(matches)?;
     }
     Ok(ProcessResult::Processed)
 }
 
-pub fn process_raw_query_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
-    let sql = matches
-        .values_of("sql")
-        .unwrap()
-        .collect::<Vec<&str>>()
-        .join(" ");
-    let db = db::DB.lock().unwrap();
-    let mut stmt: Statement = db.prepare(sql.as_str()).context("prepare error")?;
+pub fn process_status_command() -> anyhow::Result<()> {
+    let index_source = messaging::INDEX_SOURCE
+        .lock()
+        .map_err(|e| anyhow!(e.to_string()))?;
+    console::print(index_source.to_string());
+    console::print(format!("database: {}", db::DB_DESCRIPTION.lock().unwrap()));
 
-    if matches.is_present("debug") {
-        console::print(format!("stmt: {:?}", stmt));
+    for line in status::report() {
+        console::print(line);
+    }
+
+    let reconciliation = form::data_handler::RECONCILIATION
+        .lock()
+        .map_err(|e| anyhow!(e.to_string()))?;
+    for table in reconciliation.iter() {
+        console::print(table.to_string());
     }
 
+    Ok(())
+}
+
+pub fn process_worker_command() -> anyhow::Result<()> {
+    let metrics = db::WORKER_METRICS
+        .lock()
+        .map_err(|e| anyhow!(e.to_string()))?;
+    console::print(metrics.to_string());
+
+    Ok(())
+}
+
+const HISTORY_SIZE: i64 = 100;
+
+fn record_history(command_line: String) -> anyhow::Result<()> {
+    S.task_queue
+        .send(Box::new(move |db| {
+            db.prepare_cached("INSERT INTO history (command) VALUES (?)")
+                .context("record_history prepare")?
+                .execute(params![command_line])
+                .context("record_history execute")?;
+            db.prepare_cached("DELETE FROM history WHERE id <= (SELECT MAX(id) FROM history) - ?")
+                .context("record_history prepare")?
+                .execute(params![HISTORY_SIZE])
+                .context("record_history execute")?;
+            Ok(())
+        }))
+        .map_err(|e| anyhow!(e.to_string()))
+}
+
+pub fn process_history_command() -> anyhow::Result<()> {
+    let db = db::READER.lock().unwrap();
+    let mut stmt = db
+        .prepare_cached("SELECT command FROM history ORDER BY id")
+        .context("prepare error")?;
     let rows = stmt.query(NO_PARAMS).context("query error")?;
     print_rows(rows, convert_row)?;
 
     Ok(())
 }
 
-pub fn process_npc_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
-    let db = db::DB.lock().unwrap();
-    let query: String = matches
-        .values_of("query")
-        .unwrap()
-        .collect::<Vec<&str>>()
-        .join(" ");
-
-    let mut stmt;
-    let rows;
-
-    if let Ok(id) = i64::from_str_radix(query.trim_start_matches("0x"), 16) {
-        stmt = db
-            .prepare_cached(
-                "SELECT npc.*, actor.form_id as ref_id FROM npc \
-             LEFT JOIN actor ON npc.form_id = actor.base_form_id \
-             WHERE npc.editor_id LIKE ?1 OR npc.name LIKE ?1 \
-             OR npc.form_id=?2 OR actor.form_id=?2",
-            )
-            .context("prepare error")?;
-
-        if matches.is_present("debug") {
-            console::print(format!("stmt: {:?}", *stmt));
+pub fn process_bookmark_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let form_id = match matches.value_of("form_id") {
+        Some(form_id) => i64::from_str_radix(form_id.trim_start_matches("0x"), 16)
+            .context("form_id should be hexadecimal")?,
+        None => {
+            let db = db::READER.lock().unwrap();
+            let mut stmt = db
+                .prepare_cached(
+                    "SELECT bookmark.form_id, COALESCE(npc.name, cell.name, quest.name) AS name, \
+                     note FROM bookmark \
+                     LEFT JOIN actor ON bookmark.form_id = actor.form_id \
+                     LEFT JOIN npc ON npc.form_id = COALESCE(actor.base_form_id, bookmark.form_id) \
+                     LEFT JOIN cell ON cell.form_id = bookmark.form_id \
+                     LEFT JOIN quest ON quest.form_id = bookmark.form_id",
+                )
+                .context("prepare error")?;
+            let rows = stmt.query(NO_PARAMS).context("query error")?;
+            print_rows(rows, convert_row)?;
+            return Ok(());
         }
+    };
 
-        rows = stmt
-            .query(params![format!("%{}%", query), id])
-            .context("query error")?;
+    if matches.is_present("remove") {
+        S.task_queue
+            .send(Box::new(move |db| {
+                db.prepare_cached("DELETE FROM bookmark WHERE form_id = ?")
+                    .context("bookmark prepare")?
+                    .execute(params![form_id])
+                    .context("bookmark execute")?;
+                Ok(())
+            }))
+            .map_err(|e| anyhow!(e.to_string()))?;
+        console::print(format!("removed bookmark {:08X}", form_id));
     } else {
-        stmt = db
-            .prepare_cached(
-                "SELECT npc.*, actor.form_id as ref_id FROM npc \
-             LEFT JOIN actor ON npc.form_id = actor.base_form_id \
-             WHERE npc.editor_id LIKE ?1 OR npc.name LIKE ?1",
-            )
-            .context("prepare error")?;
-
-        if matches.is_present("debug") {
-            console::print(format!("stmt: {:?}", *stmt));
-        }
-
-        rows = stmt
-            .query(params![format!("%{}%", query)])
-            .context("query error")?;
+        let note = matches
+            .values_of("note")
+            .map(|note| note.collect::<Vec<&str>>().join(" "));
+        S.task_queue
+            .send(Box::new(move |db| {
+                db.prepare_cached("INSERT OR REPLACE INTO bookmark (form_id, note) VALUES (?, ?)")
+                    .context("bookmark prepare")?
+                    .execute(params![form_id, note])
+                    .context("bookmark execute")?;
+                Ok(())
+            }))
+            .map_err(|e| anyhow!(e.to_string()))?;
+        console::print(format!("bookmarked {:08X}", form_id));
     }
 
-    print_rows(rows, convert_row)?;
-
     Ok(())
 }
 
-pub fn process_cell_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
-    let db = db::DB.lock().unwrap();
-    let query: String = matches
-        .values_of("query")
+pub fn process_raw_query_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let sql = matches
+        .values_of("sql")
         .unwrap()
         .collect::<Vec<&str>>()
         .join(" ");
+    let db = db::READER.lock().unwrap();
+    let mut stmt: Statement = db.prepare(sql.as_str()).context("prepare error")?;
 
-    let mut stmt;
-    let rows;
-
-    if let Ok(id) = i64::from_str_radix(query.trim_start_matches("0x"), 16) {
-        stmt = db
-            .prepare_cached(
-                "SELECT * FROM cell WHERE editor_id LIKE ?1 OR name LIKE ?1 OR form_id=?2",
-            )
-            .context("prepare error")?;
-
-        if matches.is_present("debug") {
-            console::print(format!("stmt: {:?}", *stmt));
-        }
-
-        rows = stmt
-            .query(params![format!("%{}%", query), id])
-            .context("query error")?;
-    } else {
-        stmt = db
-            .prepare_cached("SELECT * FROM cell WHERE editor_id LIKE ?1 OR name LIKE ?1")
-            .context("prepare error")?;
-
-        if matches.is_present("debug") {
-            console::print(format!("stmt: {:?}", *stmt));
-        }
-
-        rows = stmt
-            .query(params![format!("%{}%", query)])
-            .context("query error")?;
+    if matches.is_present("debug") {
+        console::print(format!("stmt: {:?}", stmt));
     }
 
+    let rows = stmt.query(NO_PARAMS).context("query error")?;
     print_rows(rows, convert_row)?;
 
     Ok(())
 }
 
-pub fn process_quest_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
-    let db = db::DB.lock().unwrap();
-    let query: String = matches
+fn query_string(matches: &clap::ArgMatches) -> String {
+    matches
         .values_of("query")
         .unwrap()
         .collect::<Vec<&str>>()
-        .join(" ");
+        .join(" ")
+}
 
-    let mut stmt;
-    let rows;
+// Prints full text matches of `query`, or the closest fuzzy matches if there are none or
+// --fuzzy is given.
+fn print_search<F>(
+    db: &rusqlite::Connection,
+    matches: &clap::ArgMatches,
+    query: &str,
+    [sql, by_id_sql, fuzzy_sql]: [&str; 3],
+    f: F,
+) -> anyhow::Result<usize>
+where
+    F: Fn(&rusqlite::Row) -> prettytable::Row,
+{
+    let plugin = matches.value_of("plugin");
 
-    if let Ok(id) = i64::from_str_radix(query.trim_start_matches("0x"), 16) {
-        stmt = db
-            .prepare_cached(
-                "SELECT * FROM quest WHERE editor_id LIKE ?1 OR name LIKE ?1 OR form_id=?2",
-            )
-            .context("prepare error")?;
+    if !matches.is_present("fuzzy") {
+        if let Some(fts_query) = search::fts_query(query) {
+            let mut stmt;
+            let rows;
 
-        if matches.is_present("debug") {
-            console::print(format!("stmt: {:?}", *stmt));
-        }
+            if let Ok(id) = i64::from_str_radix(query.trim_start_matches("0x"), 16) {
+                stmt = db.prepare_cached(by_id_sql).context("prepare error")?;
 
-        rows = stmt
-            .query(params![format!("%{}%", query), id])
-            .context("query error")?;
-    } else {
-        stmt = db
-            .prepare_cached("SELECT * FROM quest WHERE editor_id LIKE ?1 OR name LIKE ?1")
-            .context("prepare error")?;
+                if matches.is_present("debug") {
+                    console::print(format!("stmt: {:?}", *stmt));
+                }
+
+                rows = stmt
+                    .query(params![fts_query, id, plugin])
+                    .context("query error")?;
+            } else {
+                stmt = db.prepare_cached(sql).context("prepare error")?;
+
+                if matches.is_present("debug") {
+                    console::print(format!("stmt: {:?}", *stmt));
+                }
 
-        if matches.is_present("debug") {
-            console::print(format!("stmt: {:?}", *stmt));
+                rows = stmt
+                    .query(params![fts_query, Null, plugin])
+                    .context("query error")?;
+            }
+
+            let (ptable, num_rows) = collect_rows(rows, &f)?;
+            if num_rows > 0 {
+                console::print(ptable.to_string());
+                print_index_progress();
+                return Ok(num_rows);
+            }
         }
+    }
 
-        rows = stmt
-            .query(params![format!("%{}%", query)])
-            .context("query error")?;
+    let mut stmt = db.prepare_cached(fuzzy_sql).context("prepare error")?;
+
+    if matches.is_present("debug") {
+        console::print(format!("stmt: {:?}", *stmt));
     }
 
-    print_rows(rows, convert_row)?;
+    let rows = stmt
+        .query(params![query, fuzzy::MIN_SCORE, plugin])
+        .context("query error")?;
+    let (ptable, num_rows) = collect_rows(rows, &f)?;
+    if num_rows > 0 {
+        if !matches.is_present("fuzzy") {
+            console::print(format!(
+                "No exact match for \"{}\", closest matches:",
+                query
+            ));
+        }
+        console::print(ptable.to_string());
+    } else {
+        console::print("No result");
+    }
+    print_index_progress();
 
-    Ok(())
+    Ok(num_rows)
 }
 
-pub fn process_quest_log_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
-    let db = db::DB.lock().unwrap();
-    let query: String = matches
-        .values_of("query")
-        .unwrap()
-        .collect::<Vec<&str>>()
-        .join(" ");
-
-    let mut stmt;
-    let rows;
+pub fn process_npc_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let db = db::READER.lock().unwrap();
+    let query = query_string(matches);
+
+    print_search(
+        &db,
+        matches,
+        &query,
+        [
+            search::NPC_SQL,
+            search::NPC_BY_ID_SQL,
+            search::NPC_FUZZY_SQL,
+        ],
+        convert_row,
+    )?;
 
-    if let Ok(id) = i64::from_str_radix(query.trim_start_matches("0x"), 16) {
-        stmt = db.prepare_cached(
-            "SELECT quest.*, stage, log FROM quest LEFT JOIN quest_stage ON quest.form_id = quest_stage.form_id \
-             WHERE log IS NOT NULL AND (quest.editor_id LIKE ?1 OR quest.name LIKE ?1 OR quest.form_id=?2)"
-        ).context("prepare error")?;
+    Ok(())
+}
 
-        if matches.is_present("debug") {
-            console::print(format!("stmt: {:?}", *stmt));
-        }
+pub fn process_cell_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let db = db::READER.lock().unwrap();
+    let query = query_string(matches);
+
+    print_search(
+        &db,
+        matches,
+        &query,
+        [
+            search::CELL_SQL,
+            search::CELL_BY_ID_SQL,
+            search::CELL_FUZZY_SQL,
+        ],
+        convert_row,
+    )?;
 
-        rows = stmt
-            .query(params![format!("%{}%", query), id])
-            .context("query error")?;
-    } else {
-        stmt = db
-            .prepare_cached(
-                "SELECT quest.*, stage, log FROM quest LEFT JOIN quest_stage \
-             ON quest.form_id = quest_stage.form_id \
-             WHERE log IS NOT NULL AND (quest.editor_id LIKE ?1 OR quest.name LIKE ?1)",
-            )
-            .context("prepare error")?;
-
-        if matches.is_present("debug") {
-            console::print(format!("stmt: {:?}", *stmt));
-        }
+    Ok(())
+}
 
-        rows = stmt
-            .query(params![format!("%{}%", query)])
-            .context("query error")?;
-    }
+pub fn process_quest_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let db = db::READER.lock().unwrap();
+    let query = query_string(matches);
+
+    print_search(
+        &db,
+        matches,
+        &query,
+        [
+            search::QUEST_SQL,
+            search::QUEST_BY_ID_SQL,
+            search::QUEST_FUZZY_SQL,
+        ],
+        convert_row,
+    )?;
 
-    let rows: rusqlite::Rows = rows;
+    Ok(())
+}
 
-    let num_rows = print_rows(rows, |row: &rusqlite::Row| {
-        let column_count = row.column_count();
-        let mut cells = Vec::with_capacity(column_count);
-        for i in 0..column_count - 1 {
-            let column = row.get_raw(i);
-            let repr = repr_column(row.column_name(i).ok(), column);
-            cells.push(prettytable::Cell::new(repr.as_str()));
-        }
-        let description: anyhow::Result<std::borrow::Cow<str>> = (|| {
-            let form_id = row.get_raw("form_id").as_i64()? as u32;
-            let stage = row.get_raw("stage").as_i64()?;
-            let quest: &TESQuest =
-                unsafe { &*(TESForm::look_up_by_id(form_id) as *const TESQuest) };
-            let index = quest
-                .get_log(stage as u16)
-                .ok_or_else(|| anyhow!("invalid data"))?;
-            let log_entry = if let Some(log_entry) = index.head.into_iter().next() {
-                quest.get_log_description(unsafe { &*log_entry })
-            } else {
-                std::borrow::Cow::from("")
-            };
-            Ok(log_entry)
-        })();
-
-        cells.push(prettytable::Cell::new(
-            description
-                .unwrap_or_else(|e| e.to_string().into())
-                .as_ref(),
-        ));
-        prettytable::Row::new(cells)
-    })?;
+pub fn process_quest_log_command(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let db = db::READER.lock().unwrap();
+    let query = query_string(matches);
+
+    let num_rows = print_search(
+        &db,
+        matches,
+        &query,
+        [
+            search::QUEST_STAGE_SQL,
+            search::QUEST_STAGE_BY_ID_SQL,
+            search::QUEST_STAGE_FUZZY_SQL,
+        ],
+        |row: &rusqlite::Row| {
+            let column_count = row.column_count();
+            let mut cells = Vec::with_capacity(column_count);
+            for i in 0..column_count - 1 {
+                let column = row.get_raw(i);
+                let repr = repr_column(row.column_name(i).ok(), column);
+                cells.push(prettytable::Cell::new(repr.as_str()));
+            }
+            let description: anyhow::Result<String> = (|| {
+                let form_id = row.get_raw("form_id").as_i64()? as u32;
+                let stage = row.get_raw("stage").as_i64()?;
+                search::quest_stage_log(form_id, stage as u16)
+            })();
+
+            cells.push(prettytable::Cell::new(
+                description.unwrap_or_else(|e| e.to_string()).as_str(),
+            ));
+            prettytable::Row::new(cells)
+        },
+    )?;
 
     if num_rows == 0 {
         console::print("Change your query or try loading a save?");
@@ -401,7 +536,7 @@ pub fn process_quest_log_command(matches: &clap::ArgMatches) -> anyhow::Result<(
     Ok(())
 }
 
-fn print_rows<F>(mut rows: rusqlite::Rows, f: F) -> anyhow::Result<usize>
+fn collect_rows<F>(mut rows: rusqlite::Rows, f: F) -> anyhow::Result<(prettytable::Table, usize)>
 where
     F: Fn(&rusqlite::Row) -> prettytable::Row,
 {
@@ -423,15 +558,32 @@ where
         num_rows += 1;
     }
 
+    Ok((ptable, num_rows))
+}
+
+fn print_rows<F>(rows: rusqlite::Rows, f: F) -> anyhow::Result<usize>
+where
+    F: Fn(&rusqlite::Row) -> prettytable::Row,
+{
+    let (ptable, num_rows) = collect_rows(rows, f)?;
+
     if num_rows > 0 {
         console::print(ptable.to_string());
     } else {
         console::print("No result");
     }
+    print_index_progress();
 
     Ok(num_rows)
 }
 
+// Results only include what the worker has committed so far.
+fn print_index_progress() {
+    if let Some(progress) = db::index_progress() {
+        console::print(format!("(index still building: {}%)", progress));
+    }
+}
+
 fn convert_row(row: &rusqlite::Row) -> prettytable::Row {
     let column_count = row.column_count();
     let mut cells = Vec::with_capacity(column_count);
@@ -0,0 +1,60 @@
+use crate::log::Loggable;
+use anyhow::Context;
+use lazy_static::lazy_static;
+
+const CONFIG_PATH: &str = "Data\\SKSE\\Plugins\\skyrim-search-se.ini";
+
+/// Settings from skyrim-search-se.ini. Everything is off unless enabled there.
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    /// Keep the index in a file under My Games\...\SKSE and reuse it while the load order
+    /// stays the same.
+    pub(crate) persistent_db: bool,
+    /// Write every index event to SKSE\skyrim-search-se-events.log for replaying outside the
+    /// game.
+    pub(crate) record_events: bool,
+}
+
+lazy_static! {
+    pub(crate) static ref CONFIG: Config = load().logging_ok().unwrap_or_default();
+}
+
+fn parse_bool(value: &str) -> bool {
+    matches!(
+        value.to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+// Sections are only for readability; keys are unique across the file.
+fn parse(ini: &str) -> Config {
+    let mut config = Config::default();
+    for line in ini.lines() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with(';')
+            || line.starts_with('#')
+            || line.starts_with('[')
+        {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        if key.eq_ignore_ascii_case("persistent_db") {
+            config.persistent_db = parse_bool(value);
+        } else if key.eq_ignore_ascii_case("record_events") {
+            config.record_events = parse_bool(value);
+        }
+    }
+    config
+}
+
+fn load() -> anyhow::Result<Config> {
+    match std::fs::read_to_string(CONFIG_PATH) {
+        Ok(ini) => Ok(parse(&ini)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(err).with_context(|| format!("could not read {}", CONFIG_PATH)),
+    }
+}
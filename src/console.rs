@@ -1,13 +1,17 @@
 use crate::log::Loggable;
-use crate::{app, log};
+use crate::{app, log, status};
 use anyhow::Context;
 use detour::GenericDetour;
 use late_static::LateStatic;
+use lazy_static::lazy_static;
 use std::ffi::{CStr, CString};
 use std::intrinsics::transmute;
 use std::io::Write;
+use std::ptr;
+use std::sync::Mutex;
 use winapi::_core::prelude::v1::Iterator;
 use winapi::ctypes::{c_char, c_void};
+use winapi::um::winuser::{MessageBoxA, MB_ICONWARNING, MB_OK};
 
 fn new_process_console_input(param1: usize, param2: i64, param3: i64, param4: i64) {
     let input = unsafe { CStr::from_ptr(*((param1 + 0x38) as *const *const c_char)).to_str() };
@@ -79,8 +83,45 @@ pub(crate) fn print<T: Into<Vec<u8>>>(msg: T) {
     }
 }
 
+lazy_static! {
+    static ref PENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+/// Keeps `msg` for `print_pending`, for problems found before the console exists.
+pub(crate) fn queue(msg: String) {
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.push(msg);
+    }
+}
+
+/// Prints queued messages once the game is up. Falls back to a message box when the console
+/// hook itself could not be set up.
+pub(crate) fn print_pending() {
+    let pending = match PENDING.lock() {
+        Ok(mut pending) => std::mem::take(&mut *pending),
+        Err(_) => return,
+    };
+    if pending.is_empty() {
+        return;
+    }
+    if status::is_active("console") {
+        for msg in pending {
+            print(msg);
+        }
+    } else if let Ok(text) = CString::new(pending.join("\n\n")) {
+        unsafe {
+            MessageBoxA(
+                ptr::null_mut(),
+                text.as_ptr(),
+                "Skyrim Search SE\0".as_ptr() as *const c_char,
+                MB_OK | MB_ICONWARNING,
+            );
+        }
+    }
+}
+
 pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
-    let target_addr = transmute(image_base + versionlib!(441582));
+    let target_addr = transmute(image_base + versionlib!(Console_ProcessInput)?);
     let process_console_input_hook =
         GenericDetour::<fn(usize, i64, i64, i64)>::new(target_addr, new_process_console_input)
             .context("initialize")?;
@@ -88,8 +129,8 @@ pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
     LateStatic::assign(
         &S,
         State {
-            console_context: transmute(image_base + versionlib!(401203)),
-            print_to_console: transmute(image_base + versionlib!(51109)),
+            console_context: transmute(image_base + versionlib!(ConsoleLog_Singleton)?),
+            print_to_console: transmute(image_base + versionlib!(ConsoleLog_Print)?),
             process_console_input_hook,
         },
     );
@@ -1,10 +1,21 @@
+use crate::config::CONFIG;
 use crate::log::Loggable;
+use crate::{load_order, log};
 use anyhow::Context;
 use lazy_static::lazy_static;
+use rusqlite::{OpenFlags, NO_PARAMS};
+use skyrim_search_index::{dedup, migration, register_functions, IndexEvent, Indexer};
+use std::fmt;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SendError, Sender};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use win_dbg_logger::output_debug_string;
 
 lazy_static! {
+    /// The worker's connection; everything else reads through `READER`.
     pub static ref DB: Mutex<rusqlite::Connection> = {
         match init_db().context("init_db error") {
             Ok(db) => Mutex::new(db),
@@ -15,66 +26,220 @@ lazy_static! {
             }
         }
     };
-    pub static ref TASK_QUEUE: Mutex<std::sync::mpsc::Sender<Job>> = {
+    /// Read-only connection for console commands and the C API. With WAL it sees the last
+    /// committed batch and never waits for the worker.
+    pub(crate) static ref READER: Mutex<rusqlite::Connection> = {
+        lazy_static::initialize(&DB);
+        match open_reader().context("open_reader error") {
+            Ok(reader) => Mutex::new(reader),
+            Err(err) => {
+                let s = format!("{:#}", err);
+                output_debug_string(s.as_str());
+                panic!("{}", s);
+            }
+        }
+    };
+    static ref DB_PATH: Mutex<String> = Mutex::new(String::new());
+    pub(crate) static ref DB_DESCRIPTION: Mutex<String> = Mutex::new(String::new());
+    pub(crate) static ref TASK_QUEUE: Mutex<TaskQueue> = {
         let (tx, rx) = std::sync::mpsc::channel();
-        std::thread::spawn(|| Worker(rx).worker());
-        Mutex::new(tx)
+        std::thread::spawn(|| Worker::new(rx).worker());
+        Mutex::new(TaskQueue(tx))
     };
+    pub(crate) static ref WORKER_METRICS: Mutex<WorkerMetrics> =
+        Mutex::new(WorkerMetrics::default());
 }
 
 pub(crate) type Job = Box<dyn FnOnce(&rusqlite::Connection) -> anyhow::Result<()> + Send + 'static>;
 
+/// What the worker is asked to do: index something the hooks saw, or run any other job.
+pub(crate) enum Task {
+    Index(IndexEvent),
+    Job(Job),
+}
+
+// Tasks sent but not yet committed by the worker
+static QUEUED_TASKS: AtomicUsize = AtomicUsize::new(0);
+// Tasks committed since the queue was last empty, for `index_progress`
+static COMMITTED_TASKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sending half of the worker's queue; counts queued tasks for `WorkerMetrics`.
+#[derive(Clone)]
+pub(crate) struct TaskQueue(Sender<Task>);
+
+impl TaskQueue {
+    fn send_task(&self, task: Task) -> Result<(), SendError<Task>> {
+        QUEUED_TASKS.fetch_add(1, Ordering::Relaxed);
+        self.0.send(task).inspect_err(|_| {
+            QUEUED_TASKS.fetch_sub(1, Ordering::Relaxed);
+        })
+    }
+
+    pub(crate) fn send(&self, job: Job) -> Result<(), SendError<Task>> {
+        self.send_task(Task::Job(job))
+    }
+
+    pub(crate) fn index(&self, event: IndexEvent) -> Result<(), SendError<Task>> {
+        self.send_task(Task::Index(event))
+    }
+}
+
+pub(crate) fn queued_tasks() -> usize {
+    QUEUED_TASKS.load(Ordering::Relaxed)
+}
+
+/// How far the worker got through what was queued since it was last idle, in percent, or
+/// None if it is idle.
+pub(crate) fn index_progress() -> Option<usize> {
+    let queued = queued_tasks();
+    if queued == 0 {
+        return None;
+    }
+    let committed = COMMITTED_TASKS.load(Ordering::Relaxed);
+    Some(committed * 100 / (committed + queued))
+}
+
+const DB_FILE_PREFIX: &str = "skyrim-search-se-";
+const SESSION_DB_FILE: &str = "skyrim-search-se-session.db";
+
+// Removes `file_name` in `dir` along with its -wal and -shm files.
+fn remove_db(dir: &str, file_name: &str) {
+    for suffix in ["", "-wal", "-shm"].iter() {
+        let path = format!("{}\\{}{}", dir, file_name, suffix);
+        if std::path::Path::new(&path).exists() {
+            std::fs::remove_file(path).logging_ok();
+        }
+    }
+}
+
+// Opens the opt-in persistent db, named after the load order hash, or None if not enabled.
+fn open_persistent_db() -> anyhow::Result<Option<(rusqlite::Connection, String)>> {
+    if !CONFIG.persistent_db {
+        return Ok(None);
+    }
+    let dir = log::skse_dir()?;
+    let file_name = format!("{}{:016x}.db", DB_FILE_PREFIX, load_order::hash()?);
+
+    // dbs of other load orders are never reused
+    for entry in std::fs::read_dir(&dir).context("read_dir")? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if name.starts_with(DB_FILE_PREFIX) && name.ends_with(".db") && name != file_name {
+            remove_db(&dir, &name);
+        }
+    }
+
+    let path = format!("{}\\{}", dir, file_name);
+    let conn = rusqlite::Connection::open(&path).context("open error")?;
+    Ok(Some((conn, path)))
+}
+
+fn is_complete(conn: &rusqlite::Connection) -> bool {
+    conn.query_row(
+        "SELECT value FROM meta WHERE key = 'complete'",
+        NO_PARAMS,
+        |row| row.get::<_, i64>(0),
+    )
+    .map_or(false, |complete| complete == 1)
+}
+
+/// Marks the db as fully built once data has loaded, so a persistent db can be reused.
+pub(crate) fn mark_complete(db: &rusqlite::Connection) -> anyhow::Result<()> {
+    db.execute_batch("INSERT OR REPLACE INTO meta (key, value) VALUES ('complete', 1);")
+        .context("mark_complete")?;
+    Ok(())
+}
+
+// A db for this launch only. It has to be a file rather than in memory so that `READER`
+// can open it too.
+fn open_session_db() -> anyhow::Result<(rusqlite::Connection, String)> {
+    let dir = log::skse_dir()?;
+    remove_db(&dir, SESSION_DB_FILE);
+    let path = format!("{}\\{}", dir, SESSION_DB_FILE);
+    let conn = rusqlite::Connection::open(&path).context("open error")?;
+    Ok((conn, path))
+}
+
 fn init_db() -> anyhow::Result<rusqlite::Connection> {
-    let conn = if crate::DEBUG {
-        rusqlite::Connection::open("skyrim_search_se.db").context("open error")?
+    // `path` is only set for the persistent db
+    let (conn, path) = if crate::DEBUG {
+        *DB_PATH.lock().unwrap() = String::from("skyrim_search_se.db");
+        (
+            rusqlite::Connection::open("skyrim_search_se.db").context("open error")?,
+            None,
+        )
     } else {
-        rusqlite::Connection::open("").context("open error")?
+        match open_persistent_db()
+            .context("open_persistent_db")
+            .logging_ok()
+            .flatten()
+        {
+            Some((conn, path)) => {
+                *DB_PATH.lock().unwrap() = path.clone();
+                (conn, Some(path))
+            }
+            None => {
+                let (conn, path) = open_session_db().context("open_session_db")?;
+                *DB_PATH.lock().unwrap() = path;
+                (conn, None)
+            }
+        }
     };
 
     conn.execute_batch(
         r#"
         PRAGMA mmap_size=268435456;
         PRAGMA synchronous=OFF;
-        PRAGMA journal_mode=OFF;
-
-        DROP TABLE IF EXISTS npc;
-        CREATE TABLE npc (
-            form_id INTEGER PRIMARY KEY NOT NULL,
-            editor_id TEXT COLLATE NOCASE,
-            name TEXT COLLATE NOCASE
-        );
-
-        DROP TABLE IF EXISTS actor;
-        CREATE TABLE actor (
-            form_id INTEGER PRIMARY KEY NOT NULL,
-            base_form_id INTEGER
-        );
-
-        DROP TABLE IF EXISTS cell;
-        CREATE TABLE cell (
-            form_id INTEGER PRIMARY KEY NOT NULL,
-            editor_id TEXT COLLATE NOCASE,
-            name TEXT COLLATE NOCASE
-        );
-
-        DROP TABLE IF EXISTS quest;
-        CREATE TABLE quest (
-            form_id INTEGER PRIMARY KEY NOT NULL,
-            editor_id TEXT COLLATE NOCASE,
-            name TEXT COLLATE NOCASE
-        );
-
-        DROP TABLE IF EXISTS quest_stage;
-        CREATE TABLE quest_stage (
-            form_id TEXT COLLATE NOCASE,
-            stage INTEGER,
-            log INTEGER,
-            PRIMARY KEY (form_id, stage)
-        );
+        -- readers see the last commit while the worker writes the next batch
+        PRAGMA journal_mode=WAL;
+        PRAGMA recursive_triggers=ON;
+        "#,
+    )
+    .context("init_pragma error")?;
+    register_functions(&conn).context("register_functions error")?;
+    migration::migrate(&conn).context("migrate error")?;
+
+    if let Some(path) = path.as_ref().filter(|_| is_complete(&conn)) {
+        // forms are still valid; only per-save state has to go
+        conn.execute_batch("DELETE FROM actor; DELETE FROM history; DELETE FROM bookmark;")
+            .context("reuse error")?;
+        *DB_DESCRIPTION.lock().unwrap() = format!("{} (reused)", path);
+        return Ok(conn);
+    }
+    *DB_DESCRIPTION.lock().unwrap() = match path {
+        Some(path) => format!("{} (rebuilding)", path),
+        None if crate::DEBUG => String::from("skyrim_search_se.db"),
+        None => format!("{} (this session only)", DB_PATH.lock().unwrap()),
+    };
+
+    // the triggers empty the fts tables along with these
+    conn.execute_batch(
+        r#"
+        DELETE FROM meta;
+        DELETE FROM npc;
+        DELETE FROM actor;
+        DELETE FROM cell;
+        DELETE FROM quest;
+        DELETE FROM quest_stage;
+        DELETE FROM history;
+        DELETE FROM bookmark;
+        DELETE FROM plugin;
         "#,
     )
-    .context("init_schema error")?;
+    .context("clear error")?;
+
+    Ok(conn)
+}
 
+fn open_reader() -> anyhow::Result<rusqlite::Connection> {
+    let path = DB_PATH.lock().unwrap().clone();
+    let conn = rusqlite::Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("open {}", path))?;
+    conn.execute_batch("PRAGMA mmap_size=268435456;")
+        .context("reader pragma error")?;
+    register_functions(&conn).context("register_functions error")?;
     Ok(conn)
 }
 
@@ -97,25 +262,207 @@ pub(crate) fn init_index(db: &rusqlite::Connection) -> rusqlite::Result<()> {
     )
 }
 
-struct Worker(std::sync::mpsc::Receiver<Job>);
+const EVENTS_FILE: &str = "\\skyrim-search-se-events.log";
+
+// A batch is committed once it has this many tasks or took this long, so that console
+// queries see a long data load progress.
+const MAX_BATCH_TASKS: usize = 10000;
+const MAX_BATCH_TIME: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Default)]
+struct Batch {
+    jobs: usize,
+    events: usize,
+    duplicate_events: usize,
+    failed: usize,
+}
+
+impl Batch {
+    fn tasks(&self) -> usize {
+        self.jobs + self.events
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct WorkerMetrics {
+    batches: u64,
+    jobs: u64,
+    events: u64,
+    duplicate_events: u64,
+    failed: u64,
+    max_batch_tasks: usize,
+    busy_time: Duration,
+    max_batch_time: Duration,
+    max_queued_tasks: usize,
+}
+
+impl WorkerMetrics {
+    fn record(&mut self, batch: &Batch, time: Duration, queued_tasks: usize) {
+        self.batches += 1;
+        self.jobs += batch.jobs as u64;
+        self.events += batch.events as u64;
+        self.duplicate_events += batch.duplicate_events as u64;
+        self.failed += batch.failed as u64;
+        self.max_batch_tasks = self.max_batch_tasks.max(batch.tasks());
+        self.busy_time += time;
+        self.max_batch_time = self.max_batch_time.max(time);
+        self.max_queued_tasks = self.max_queued_tasks.max(queued_tasks);
+    }
+}
+
+impl fmt::Display for WorkerMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let batches = self.batches.max(1);
+        writeln!(
+            f,
+            "{} index events ({} duplicates skipped) and {} jobs, {} failed",
+            self.events, self.duplicate_events, self.jobs, self.failed
+        )?;
+        writeln!(f, "{} batches, {:?} busy", self.batches, self.busy_time)?;
+        writeln!(
+            f,
+            "tasks per batch: avg {}, max {}",
+            (self.events + self.jobs) / batches,
+            self.max_batch_tasks
+        )?;
+        writeln!(
+            f,
+            "time per batch: avg {:?}, max {:?}",
+            self.busy_time / batches as u32,
+            self.max_batch_time
+        )?;
+        write!(
+            f,
+            "queued tasks: {} now, max {}",
+            queued_tasks(),
+            self.max_queued_tasks
+        )
+    }
+}
+
+struct Worker {
+    task_queue: Receiver<Task>,
+    // every event received, for replaying with skyrim-search-index
+    recorder: Option<LineWriter<File>>,
+}
 
 impl Worker {
-    fn worker(self) {
-        let task_queue = self.0;
+    fn new(task_queue: Receiver<Task>) -> Self {
+        let recorder = if CONFIG.record_events {
+            (|| -> anyhow::Result<_> {
+                let file = File::create(log::skse_dir()? + EVENTS_FILE)?;
+                Ok(LineWriter::new(file))
+            })()
+            .context("open events file")
+            .logging_ok()
+        } else {
+            None
+        };
+        Worker {
+            task_queue,
+            recorder,
+        }
+    }
+
+    fn worker(mut self) {
         loop {
-            let job = task_queue.recv().unwrap();
+            let mut task = self.task_queue.recv().unwrap();
+            let queued_tasks = queued_tasks();
             let db = DB.lock().unwrap();
-            let mut num_jobs = 1;
-            Self::process_job(&db, job).logging_ok();
-            for job in task_queue.try_iter() {
-                num_jobs += 1;
-                Self::process_job(&db, job).logging_ok();
+            let start = Instant::now();
+            let mut batch = Batch::default();
+            // consecutive events are deduplicated and applied together
+            let mut events = Vec::new();
+
+            db.execute_batch("BEGIN;")
+                .context("begin batch")
+                .logging_ok();
+            loop {
+                match task {
+                    Task::Index(event) => {
+                        self.record(&event);
+                        batch.events += 1;
+                        events.push(event);
+                    }
+                    Task::Job(job) => {
+                        Self::apply_events(&db, &mut events, &mut batch);
+                        batch.jobs += 1;
+                        if Self::process_job(&db, job).logging_ok().is_none() {
+                            batch.failed += 1;
+                        }
+                    }
+                }
+                if batch.tasks() >= MAX_BATCH_TASKS || start.elapsed() >= MAX_BATCH_TIME {
+                    break;
+                }
+                task = match self.task_queue.try_recv() {
+                    Ok(task) => task,
+                    Err(_) => break,
+                };
+            }
+            Self::apply_events(&db, &mut events, &mut batch);
+            db.execute_batch("COMMIT;")
+                .context("commit batch")
+                .logging_ok();
+            drop(db);
+            // tasks count as queued until READER can see them
+            if QUEUED_TASKS.fetch_sub(batch.tasks(), Ordering::Relaxed) == batch.tasks() {
+                COMMITTED_TASKS.store(0, Ordering::Relaxed);
+            } else {
+                COMMITTED_TASKS.fetch_add(batch.tasks(), Ordering::Relaxed);
             }
-            output_debug_string(format!("processed {} jobs", num_jobs).as_str());
+
+            let time = start.elapsed();
+            WORKER_METRICS
+                .lock()
+                .unwrap()
+                .record(&batch, time, queued_tasks);
+            output_debug_string(
+                format!("processed {} tasks in {:?}", batch.tasks(), time).as_str(),
+            );
         }
     }
 
+    fn record(&mut self, event: &IndexEvent) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if writeln!(recorder, "{}", event)
+                .context("record event")
+                .logging_ok()
+                .is_none()
+            {
+                self.recorder = None;
+            }
+        }
+    }
+
+    fn apply_events(db: &rusqlite::Connection, events: &mut Vec<IndexEvent>, batch: &mut Batch) {
+        let received = events.len();
+        let events = dedup(std::mem::take(events));
+        batch.duplicate_events += received - events.len();
+
+        let indexer = Indexer::new(db);
+        for event in events.iter() {
+            if indexer.apply(event).logging_ok().is_none() {
+                batch.failed += 1;
+            }
+        }
+    }
+
+    // Each job runs in a savepoint, so a failing job leaves nothing behind and does not take
+    // the rest of the batch with it.
     fn process_job(db: &rusqlite::Connection, msg: Job) -> anyhow::Result<()> {
-        msg(db)
+        db.execute_batch("SAVEPOINT job;").context("savepoint")?;
+        match msg(db) {
+            Ok(()) => {
+                db.execute_batch("RELEASE job;").context("release")?;
+                Ok(())
+            }
+            Err(err) => {
+                db.execute_batch("ROLLBACK TO job; RELEASE job;")
+                    .context("rollback")
+                    .logging_ok();
+                Err(err)
+            }
+        }
     }
 }
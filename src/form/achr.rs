@@ -1,15 +1,14 @@
 use crate::db;
-use crate::db::Job;
+use crate::db::TaskQueue;
 use crate::form::refr::TESObjectREFR;
 use crate::log::Loggable;
 use crate::patch::patch_bytes;
-use anyhow::{anyhow, Context};
+use anyhow::anyhow;
 use late_static::LateStatic;
-use rusqlite::params;
+use skyrim_search_index::IndexEvent;
 use std::fmt::Formatter;
 use std::mem::transmute;
 use std::ops::Deref;
-use std::sync::mpsc::Sender;
 use win_dbg_logger::output_debug_string;
 use winapi::ctypes::c_void;
 
@@ -18,7 +17,7 @@ struct TESCharacter(TESObjectREFR);
 struct State {
     character_vtable: usize,
     character_load: fn(&TESCharacter, u64) -> u64,
-    task_queue: Sender<Job>,
+    task_queue: TaskQueue,
 }
 unsafe impl Sync for State {}
 static S: LateStatic<State> = LateStatic::new();
@@ -41,27 +40,19 @@ impl TESCharacter {
             return ret;
         };
         let form_id = self.0.form.form_id;
-        let result: anyhow::Result<()> = (|| {
-            S.task_queue
-                .send(Box::new(move |db| {
-                    db.prepare_cached(
-                        "INSERT OR REPLACE INTO actor (form_id, base_form_id) VALUES (?, ?);",
-                    )
-                    .context("chracter_new_load prepare")?
-                    .execute(params![form_id, base_form.form_id])
-                    .context("character_new_load execute")?;
-                    Ok(())
-                }))
-                .map_err(|e| anyhow!(e.to_string()))?;
-            Ok(())
-        })();
-        result.logging_ok();
+        S.task_queue
+            .index(IndexEvent::ActorRef {
+                form_id,
+                base_form_id: base_form.form_id,
+            })
+            .map_err(|e| anyhow!(e.to_string()))
+            .logging_ok();
         ret
     }
 }
 
 pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
-    let character_vtable = transmute(image_base + versionlib!(207886));
+    let character_vtable = transmute(image_base + versionlib!(Character_vtbl)?);
 
     let original_character_load = patch_bytes(
         &(TESCharacter::new_load as usize),
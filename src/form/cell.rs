@@ -1,16 +1,15 @@
 use crate::db;
-use crate::db::Job;
+use crate::db::TaskQueue;
 use crate::form::TESForm;
 use crate::log::Loggable;
 use crate::patch::patch_bytes;
-use anyhow::{anyhow, Context};
+use anyhow::anyhow;
 use late_static::LateStatic;
-use rusqlite::params;
+use skyrim_search_index::IndexEvent;
 use std::ffi::CStr;
 use std::fmt::Formatter;
 use std::mem::transmute;
 use std::ops::Deref;
-use std::sync::mpsc::Sender;
 use win_dbg_logger::output_debug_string;
 use winapi::ctypes::{c_char, c_void};
 
@@ -20,7 +19,7 @@ struct State {
     cell_vtable: usize,
     cell_load: fn(&TESObjectCELL, u64) -> u64,
     cell_get_edid: fn(&TESObjectCELL) -> *const c_char,
-    task_queue: Sender<Job>,
+    task_queue: TaskQueue,
 }
 unsafe impl Sync for State {}
 static S: LateStatic<State> = LateStatic::new();
@@ -50,26 +49,20 @@ impl TESObjectCELL {
         let form_id = self.0.form_id;
         let editor_id = self.get_edid().map(|name| name.to_string());
         let name = self.0.get_name().map(|name| name.to_string());
-        let result: anyhow::Result<()> =
-            (|| {
-                S.task_queue
-                    .send(Box::new(move |db| {
-                        db.prepare_cached(
-                "INSERT OR REPLACE INTO cell (form_id, editor_id, name) VALUES (?, ?, ?);",
-            ).context("cell_new_load prepare")?
-                .execute(params![form_id, editor_id, name]).context("cell_new_load execute")?;
-                        Ok(())
-                    }))
-                    .map_err(|e| anyhow!(e.to_string()))?;
-                Ok(())
-            })();
-        result.logging_ok();
+        S.task_queue
+            .index(IndexEvent::Cell {
+                form_id,
+                editor_id,
+                name,
+            })
+            .map_err(|e| anyhow!(e.to_string()))
+            .logging_ok();
         ret
     }
 }
 
 pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
-    let cell_vtable = transmute(image_base + versionlib!(190143));
+    let cell_vtable = transmute(image_base + versionlib!(TESObjectCELL_vtbl)?);
 
     let original_cell_load = patch_bytes(
         &(TESObjectCELL::new_load as usize),
@@ -0,0 +1,304 @@
+use crate::db;
+use crate::db::TaskQueue;
+use crate::form::qust::TESQuest;
+use crate::form::TESForm;
+use crate::log::Loggable;
+use anyhow::{anyhow, Context};
+use late_static::LateStatic;
+use lazy_static::lazy_static;
+use rusqlite::{params, NO_PARAMS};
+use skyrim_search_index::{FormTable, IndexEvent};
+use std::ffi::CStr;
+use std::fmt;
+use std::fmt::Formatter;
+use std::io::Write;
+use std::mem::transmute;
+use std::ops::Deref;
+use std::sync::Mutex;
+use win_dbg_logger::output_debug_string;
+use winapi::ctypes::c_char;
+
+const FORM_TYPE_NPC: usize = 0x2B;
+const FORM_TYPE_CELL: usize = 0x3C;
+const FORM_TYPE_QUEST: usize = 0x4D;
+
+#[allow(dead_code)]
+#[repr(C)]
+struct BSTArray<T> {
+    data: *const *const T, // 00
+    capacity: u32,         // 08
+    padding_0c: u32,
+    size: u32, // 10
+    padding_14: u32,
+    // 18
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct NiTPrimitiveArray {
+    vtable: usize,               // 00
+    data: *const *const TESForm, // 08
+    capacity: u16,               // 10
+    free_index: u16,             // 12
+    size: u16,                   // 14
+    growth_size: u16,            // 16
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct TESFile {
+    unknown_00: [u8; 0x58],
+    file_name: [c_char; 0x104], // 058
+    unknown_15c: [u8; 0x31C],
+    compile_index: u8, // 478
+    padding_479: u8,
+    small_file_compile_index: u16, // 47A
+}
+
+// Only the members we read; TESDataHandler is far larger.
+#[allow(dead_code)]
+#[repr(C)]
+struct TESDataHandler {
+    unknown_00: [u8; 0x10],
+    form_arrays: [BSTArray<TESForm>; 0x8A], // 010
+    region_list: usize,                     // D00
+    interior_cells: NiTPrimitiveArray,      // D08
+    addon_nodes: NiTPrimitiveArray,         // D20
+    // bad forms, next id, active file and the list of all files
+    unknown_d38: [u8; 0x38],
+    // compiled file collection: the loaded plugins and light plugins
+    files: BSTArray<TESFile>,       // D70
+    small_files: BSTArray<TESFile>, // D88
+}
+
+impl<T> BSTArray<T> {
+    fn items(&self) -> impl Iterator<Item = &T> {
+        let data = self.data;
+        let len = if data.is_null() {
+            0
+        } else {
+            self.size as usize
+        };
+        (0..len).filter_map(move |i| unsafe { (*data.add(i)).as_ref() })
+    }
+}
+
+impl NiTPrimitiveArray {
+    fn forms(&self) -> impl Iterator<Item = &TESForm> {
+        let data = self.data;
+        let len = if data.is_null() {
+            0
+        } else {
+            self.free_index as usize
+        };
+        (0..len).filter_map(move |i| unsafe { (*data.add(i)).as_ref() })
+    }
+}
+
+struct State {
+    data_handler: *const *const TESDataHandler,
+    task_queue: TaskQueue,
+}
+unsafe impl Sync for State {}
+static S: LateStatic<State> = LateStatic::new();
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("data_handler", &(self.data_handler as usize))
+            .finish()
+    }
+}
+
+/// How many forms of a table the `Load` hooks and the enumeration found.
+#[derive(Debug, Clone)]
+pub(crate) struct Reconciliation {
+    table: &'static str,
+    hooked: i64,
+    enumerated: i64,
+    hooked_only: i64,
+    enumerated_only: i64,
+}
+
+lazy_static! {
+    pub(crate) static ref RECONCILIATION: Mutex<Vec<Reconciliation>> = Mutex::new(Vec::new());
+}
+
+impl fmt::Display for Reconciliation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: load hook {}, data handler {} (only load hook {}, only data handler {})",
+            self.table, self.hooked, self.enumerated, self.hooked_only, self.enumerated_only
+        )
+    }
+}
+
+fn enumerated(table: FormTable, form: &TESForm) -> IndexEvent {
+    IndexEvent::EnumeratedForm {
+        table,
+        form_id: form.form_id,
+        editor_id: form.get_edid().map(|s| s.to_string()),
+        name: form.get_name().map(|s| s.to_string()),
+    }
+}
+
+// Compares the form ids of `table` with the enumerated ones, before those are indexed.
+fn reconcile(
+    db: &rusqlite::Connection,
+    table: FormTable,
+    form_ids: &[u32],
+) -> anyhow::Result<Reconciliation> {
+    let table = table.name();
+    db.execute_batch(
+        "DROP TABLE IF EXISTS temp.enumerated; \
+         CREATE TEMP TABLE enumerated (form_id INTEGER PRIMARY KEY NOT NULL);",
+    )
+    .context("reconcile create")?;
+    {
+        let mut stmt = db
+            .prepare("INSERT OR IGNORE INTO temp.enumerated (form_id) VALUES (?)")
+            .context("reconcile prepare")?;
+        for form_id in form_ids {
+            stmt.execute(params![form_id])
+                .context("reconcile execute")?;
+        }
+    }
+
+    let count = |sql: String| -> anyhow::Result<i64> {
+        db.query_row(sql.as_str(), NO_PARAMS, |row| row.get(0))
+            .context("reconcile count")
+    };
+    let reconciliation = Reconciliation {
+        table,
+        hooked: count(format!("SELECT COUNT(*) FROM {}", table))?,
+        enumerated: count("SELECT COUNT(*) FROM temp.enumerated".to_string())?,
+        hooked_only: count(format!(
+            "SELECT COUNT(*) FROM {} WHERE form_id NOT IN (SELECT form_id FROM temp.enumerated)",
+            table
+        ))?,
+        enumerated_only: count(format!(
+            "SELECT COUNT(*) FROM temp.enumerated WHERE form_id NOT IN (SELECT form_id FROM {})",
+            table
+        ))?,
+    };
+
+    db.execute_batch("DROP TABLE temp.enumerated;")
+        .context("reconcile drop")?;
+
+    Ok(reconciliation)
+}
+
+/// Indexes every plugin, npc, interior cell and quest the data handler knows about and records how
+/// that compares to what the `Load` hooks indexed. Must run on the main thread after
+/// kDataLoaded. Exterior cells are not listed by the data handler and still come from the hook.
+pub(crate) fn index_all() -> anyhow::Result<()> {
+    let data_handler = unsafe { (*S.data_handler).as_ref() }
+        .ok_or_else(|| anyhow!("TESDataHandler is not initialized"))?;
+
+    // light plugins share the FE load order slot; their forms are FExxxyyy
+    let plugins = data_handler
+        .files
+        .items()
+        .map(|file| (file.compile_index as u32, file))
+        .chain(
+            data_handler
+                .small_files
+                .items()
+                .map(|file| (0xFE000 | file.small_file_compile_index as u32, file)),
+        );
+    for (id, file) in plugins {
+        let file_name = unsafe { CStr::from_ptr(file.file_name.as_ptr()) }
+            .to_string_lossy()
+            .to_string();
+        S.task_queue
+            .index(IndexEvent::Plugin { id, file_name })
+            .map_err(|e| anyhow!(e.to_string()))?;
+    }
+
+    let mut events = Vec::new();
+    events.extend(
+        data_handler.form_arrays[FORM_TYPE_NPC]
+            .items()
+            .map(|form| enumerated(FormTable::Npc, form)),
+    );
+    events.extend(
+        data_handler.form_arrays[FORM_TYPE_CELL]
+            .items()
+            .chain(data_handler.interior_cells.forms())
+            .map(|form| enumerated(FormTable::Cell, form)),
+    );
+    for form in data_handler.form_arrays[FORM_TYPE_QUEST].items() {
+        events.push(enumerated(FormTable::Quest, form));
+        let quest = unsafe { &*(form as *const TESForm as *const TESQuest) };
+        for (stage, log) in quest.stage_logs() {
+            events.push(IndexEvent::QuestStage {
+                form_id: form.form_id,
+                stage,
+                log,
+            });
+        }
+    }
+
+    let form_ids = |table: FormTable| -> Vec<u32> {
+        events
+            .iter()
+            .filter_map(|event| match *event {
+                IndexEvent::EnumeratedForm {
+                    table: t, form_id, ..
+                } if t == table => Some(form_id),
+                _ => None,
+            })
+            .collect()
+    };
+    let (npcs, cells, quests) = (
+        form_ids(FormTable::Npc),
+        form_ids(FormTable::Cell),
+        form_ids(FormTable::Quest),
+    );
+
+    // the worker runs the job before it applies the events sent after it
+    S.task_queue
+        .send(Box::new(move |db| {
+            let reconciliations = vec![
+                reconcile(db, FormTable::Npc, &npcs)?,
+                reconcile(db, FormTable::Cell, &cells)?,
+                reconcile(db, FormTable::Quest, &quests)?,
+            ];
+
+            if let Ok(mut w) = crate::log::LOG.lock() {
+                for reconciliation in reconciliations.iter() {
+                    w.write_all(format!("index_all {}\n", reconciliation).as_bytes())
+                        .logging_ok();
+                }
+            }
+            *RECONCILIATION.lock().map_err(|e| anyhow!(e.to_string()))? = reconciliations;
+
+            Ok(())
+        }))
+        .map_err(|e| anyhow!(e.to_string()))?;
+
+    for event in events {
+        S.task_queue
+            .index(event)
+            .map_err(|e| anyhow!(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
+    let data_handler = transmute(image_base + versionlib!(TESDataHandler_Singleton)?);
+
+    LateStatic::assign(
+        &S,
+        State {
+            data_handler,
+            task_queue: db::TASK_QUEUE.lock().unwrap().clone(),
+        },
+    );
+
+    output_debug_string(format!("S: {:#x?}", S.deref()).as_str());
+
+    Ok(())
+}
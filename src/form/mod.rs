@@ -1,4 +1,4 @@
-use anyhow::Context;
+use crate::status;
 use late_static::LateStatic;
 use std::ffi::CStr;
 use std::mem::transmute;
@@ -6,6 +6,7 @@ use winapi::ctypes::c_char;
 
 mod achr;
 mod cell;
+pub(crate) mod data_handler;
 mod npc;
 pub(crate) mod qust;
 mod refr;
@@ -42,14 +43,28 @@ impl TESForm {
         }
     }
 
+    // TESForm::GetFormEditorID; most forms only keep their editor id when it is used by scripts
+    pub(crate) fn get_edid(&self) -> Option<std::borrow::Cow<str>> {
+        unsafe {
+            let vtable = *(self as *const TESForm as *const usize);
+            let get_edid: fn(&TESForm) -> *const c_char =
+                transmute(*((vtable + 0x190) as *const usize));
+            let result = get_edid(self);
+            if result.is_null() || *result == 0 {
+                return None;
+            }
+            Some(CStr::from_ptr(result).to_string_lossy())
+        }
+    }
+
     pub(crate) fn look_up_by_id(id: u32) -> *const TESForm {
         (S.look_up_by_id)(id)
     }
 }
 
-pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
-    let get_name = transmute(image_base + versionlib!(14720));
-    let look_up_by_id = transmute(image_base + versionlib!(14617));
+unsafe fn init_form(image_base: usize) -> anyhow::Result<()> {
+    let get_name = transmute(image_base + versionlib!(TESForm_GetName)?);
+    let look_up_by_id = transmute(image_base + versionlib!(TESForm_LookupByID)?);
 
     LateStatic::assign(
         &S,
@@ -59,10 +74,16 @@ pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
         },
     );
 
-    npc::init(image_base).context("npc::init")?;
-    achr::init(image_base).context("achr::init")?;
-    cell::init(image_base).context("cell::init")?;
-    qust::init(image_base).context("qust::init")?;
-
     Ok(())
 }
+
+pub(crate) unsafe fn init(image_base: usize) {
+    status::init("form", &["address"], || init_form(image_base));
+    status::init("form::npc", &["form"], || npc::init(image_base));
+    status::init("form::achr", &["form"], || achr::init(image_base));
+    status::init("form::cell", &["form"], || cell::init(image_base));
+    status::init("form::qust", &["form"], || qust::init(image_base));
+    status::init("form::data_handler", &["form"], || {
+        data_handler::init(image_base)
+    });
+}
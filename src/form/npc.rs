@@ -1,16 +1,15 @@
 use crate::db;
-use crate::db::Job;
+use crate::db::TaskQueue;
 use crate::form::TESForm;
 use crate::log::Loggable;
-use crate::patch::patch_bytes;
-use anyhow::{anyhow, Context};
+use crate::patch::{patch_bytes, restore_bytes};
+use anyhow::anyhow;
 use late_static::LateStatic;
-use rusqlite::params;
+use skyrim_search_index::IndexEvent;
 use std::ffi::CStr;
 use std::fmt::Formatter;
 use std::mem::transmute;
 use std::ops::Deref;
-use std::sync::mpsc::Sender;
 use win_dbg_logger::output_debug_string;
 use winapi::ctypes::{c_char, c_void};
 
@@ -20,7 +19,7 @@ struct TESNPC(TESForm);
 struct State {
     npc_vtable: usize,
     npc_load: fn(&TESNPC, u64) -> u64,
-    task_queue: Sender<Job>,
+    task_queue: TaskQueue,
 }
 unsafe impl Sync for State {}
 static S: LateStatic<State> = LateStatic::new();
@@ -44,16 +43,10 @@ impl TESNPC {
             let edid = unsafe { CStr::from_ptr(edid).to_str()? }.to_string();
 
             S.task_queue
-                .send(Box::new(move |db| {
-                    db.prepare_cached(
-                        "INSERT INTO npc (form_id, editor_id) VALUES (?, ?)\
-                     ON CONFLICT(form_id) DO UPDATE SET editor_id=excluded.editor_id",
-                    )
-                    .context("npc_set_edid prepare")?
-                    .execute(params![form_id, edid])
-                    .context("npc_set_edid execute")?;
-                    Ok(())
-                }))
+                .index(IndexEvent::NpcEditorId {
+                    form_id,
+                    editor_id: edid,
+                })
                 .map_err(|e| anyhow!(e.to_string()))?;
 
             Ok(())
@@ -68,16 +61,7 @@ impl TESNPC {
             let result: anyhow::Result<()> = (|| {
                 let name = name.to_string();
                 S.task_queue
-                    .send(Box::new(move |db| {
-                        db.prepare_cached(
-                            "INSERT INTO npc (form_id, name) VALUES (?, ?)\
-                         ON CONFLICT(form_id) DO UPDATE SET name=excluded.name",
-                        )
-                        .context("npc_new_load prepare")?
-                        .execute(params![form_id, name])
-                        .context("npc_new_load execute")?;
-                        Ok(())
-                    }))
+                    .index(IndexEvent::NpcName { form_id, name })
                     .map_err(|e| anyhow!(e.to_string()))?;
 
                 Ok(())
@@ -89,20 +73,26 @@ impl TESNPC {
 }
 
 pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
-    let npc_vtable = transmute(image_base + versionlib!(195816));
+    let npc_vtable = transmute(image_base + versionlib!(TESNPC_vtbl)?);
 
     output_debug_string(format!("npc set_edid: {:#x}", npc_vtable + 0x198).as_str());
 
-    patch_bytes(
+    let original_npc_set_edid = patch_bytes(
         &(TESNPC::new_set_edid as usize),
         (npc_vtable + 0x198) as *mut c_void,
         8,
     )?;
-    let original_npc_load = patch_bytes(
+    let original_npc_load = match patch_bytes(
         &(TESNPC::new_load as usize),
         (npc_vtable + 0x30) as *mut c_void,
         8,
-    )?;
+    ) {
+        Ok(original_npc_load) => original_npc_load,
+        Err(err) => {
+            restore_bytes(&original_npc_set_edid, (npc_vtable + 0x198) as *mut c_void).logging_ok();
+            return Err(err);
+        }
+    };
 
     LateStatic::assign(
         &S,
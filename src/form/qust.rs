@@ -1,16 +1,15 @@
 use crate::db;
-use crate::db::Job;
+use crate::db::TaskQueue;
 use crate::form::TESForm;
 use crate::log::Loggable;
 use crate::patch::patch_bytes;
-use anyhow::{anyhow, Context};
+use anyhow::anyhow;
 use late_static::LateStatic;
-use rusqlite::params;
+use skyrim_search_index::IndexEvent;
 use std::ffi::CStr;
 use std::fmt::Formatter;
 use std::mem::transmute;
 use std::ops::Deref;
-use std::sync::mpsc::Sender;
 use win_dbg_logger::output_debug_string;
 use winapi::ctypes::{c_char, c_void};
 
@@ -58,7 +57,7 @@ struct State {
     quest_get_edid: fn(&TESQuest) -> *const c_char,
     #[allow(dead_code)]
     quest_get_description: fn(&LogEntry, &TESQuest, u64, u64) -> *const c_char,
-    task_queue: Sender<Job>,
+    task_queue: TaskQueue,
 }
 unsafe impl Sync for State {}
 static S: LateStatic<State> = LateStatic::new();
@@ -166,6 +165,20 @@ impl TESQuest {
         vec
     }
 
+    // (stage, log string offset) of every log entry that has a description
+    pub(crate) fn stage_logs(&self) -> Vec<(u16, u32)> {
+        let mut stage_logs = Vec::new();
+        for (index, log_entries) in self.traverse().iter() {
+            for log in log_entries.iter() {
+                if log.string_offset == 4294967295 {
+                    continue;
+                }
+                stage_logs.push((index.stage, log.string_offset));
+            }
+        }
+        stage_logs
+    }
+
     pub(crate) fn get_log(&self, stage: u16) -> Option<&Index> {
         unsafe {
             let head = self.get_head()?;
@@ -195,35 +208,21 @@ impl TESQuest {
         let name = self.0.get_name().map(|name| name.to_string());
         let result: anyhow::Result<()> = (|| {
             S.task_queue
-                .send(Box::new(move |db| {
-                    db.prepare_cached(
-                        "INSERT OR REPLACE INTO quest (form_id, editor_id, name) VALUES (?, ?, ?);",
-                    )
-                    .context("quest_new_load prepare")?
-                    .execute(params![form_id, editor_id, name])
-                    .context("quest_new_load execute")?;
-
-                    Ok(())
-                }))
+                .index(IndexEvent::Quest {
+                    form_id,
+                    editor_id,
+                    name,
+                })
                 .map_err(|e| anyhow!(e.to_string()))?;
 
-            for (index, log_entries) in self.traverse().iter() {
-                for log in log_entries.iter() {
-                    let stage = index.stage;
-                    let log_string_offset = log.string_offset;
-                    if log_string_offset == 4294967295 {
-                        continue;
-                    }
-                    S.task_queue.send(Box::new(move |db| {
-                        db.prepare_cached(
-                            "INSERT OR REPLACE INTO quest_stage (form_id, stage, log) VALUES (?, ?, ?);",
-                        ).context("quest_new_load prepare")?
-                            .execute(params![form_id, stage, log_string_offset])
-                            .context("quest_new_load execute")?;
-
-                        Ok(())
-                    })).map_err(|e| anyhow!(e.to_string()))?;
-                }
+            for (stage, log) in self.stage_logs() {
+                S.task_queue
+                    .index(IndexEvent::QuestStage {
+                        form_id,
+                        stage,
+                        log,
+                    })
+                    .map_err(|e| anyhow!(e.to_string()))?;
             }
 
             Ok(())
@@ -234,8 +233,8 @@ impl TESQuest {
 }
 
 pub(crate) unsafe fn init(image_base: usize) -> anyhow::Result<()> {
-    let quest_vtable = transmute(image_base + versionlib!(195890));
-    let quest_get_description = transmute(image_base + versionlib!(25259));
+    let quest_vtable = transmute(image_base + versionlib!(TESQuest_vtbl)?);
+    let quest_get_description = transmute(image_base + versionlib!(TESQuest_GetLogDescription)?);
 
     let original_quest_load = patch_bytes(
         &(TESQuest::new_load as usize),
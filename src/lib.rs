@@ -1,15 +1,23 @@
 #[macro_use]
 extern crate versionlib_macro;
 
+mod address;
+mod api;
 mod app;
+mod config;
 mod console;
 mod db;
 mod form;
+mod load_order;
 mod log;
+mod messaging;
+mod papyrus;
 mod patch;
+mod search;
+mod serialization;
+mod status;
 
 use crate::log::Loggable;
-use anyhow::Context;
 use std::fmt::{Debug, Formatter};
 use std::io::Write;
 use std::{fmt, ptr};
@@ -25,6 +33,12 @@ enum DataVersion {
     KVersion = 1,
 }
 
+enum VersionIndependence {
+    AddressLibraryPostAE = 1 << 0,
+    // form, quest and data handler layouts as of 1.6.629
+    StructsPost629 = 1 << 2,
+}
+
 #[allow(non_snake_case)]
 #[repr(C)]
 pub struct SKSEPluginVersionData {
@@ -34,8 +48,9 @@ pub struct SKSEPluginVersionData {
     name: [u8; 256],
 
     author: [u8; 256],
-    supportEmail: [u8; 256],
+    supportEmail: [u8; 252],
 
+    versionIndependenceEx: u32,
     versionIndependence: u32,
     compatibleVersions: [u32; 16],
 
@@ -59,25 +74,10 @@ pub static SKSEPlugin_Version: SKSEPluginVersionData = SKSEPluginVersionData {
     name: zero_pad_u8(b"Skyrim Search SE\0"),
     author: zero_pad_u8(b"qbx2, lukasaldersley\0"),
     supportEmail: zero_pad_u8(b"open a GitHub issue on qbx2's GitHub\0"),
-    versionIndependence: 0,
-    compatibleVersions: [
-        target_version!(),
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-        0,
-    ],
+    versionIndependenceEx: 0,
+    versionIndependence: VersionIndependence::AddressLibraryPostAE as u32
+        | VersionIndependence::StructsPost629 as u32,
+    compatibleVersions: [0; 16],
     seVersionRequired: 0,
 };
 
@@ -120,27 +120,26 @@ pub extern "C" fn SKSEPlugin_Load(skse: *const SKSEInterface) -> bool {
     lazy_static::initialize(&log::LOG);
     output_debug_string(format!("ssse skse load: {:#?}", skse).as_str());
 
-    let result: anyhow::Result<()> = (|| {
-        unsafe {
-            let image_base = GetModuleHandleA(ptr::null()) as usize;
-
-            console::init(image_base).context("console::init")?;
-            form::init(image_base).context("form::init")?;
-            app::init(image_base).context("app::init")?;
-        }
-
-        Ok(())
-    })();
+    // Every subsystem initializes on its own so that one unsupported hook does not take the
+    // others down with it; `ss status` shows the outcome.
+    unsafe {
+        let image_base = GetModuleHandleA(ptr::null()) as usize;
+
+        status::init("address", &[], || address::init(skse.runtime_version));
+        status::init("app", &[], || app::init(image_base));
+        status::init("console", &["address", "app"], || console::init(image_base));
+        form::init(image_base);
+        status::init("messaging", &[], || messaging::init(skse));
+        status::init("papyrus", &["address"], || papyrus::init(skse, image_base));
+        status::init("serialization", &[], || serialization::init(skse));
+    }
 
-    lazy_static::initialize(&db::DB);
+    lazy_static::initialize(&db::READER);
 
-    if let Err(err) = result {
-        log::LOG
-            .lock()
-            .unwrap()
-            .write_all(format!("error SKSEPlugin_Load: {}\n", err).as_bytes())
-            .unwrap();
-        return false;
+    if let Ok(mut w) = log::LOG.lock() {
+        for line in status::report() {
+            w.write_all(format!("{}\n", line).as_bytes()).logging_ok();
+        }
     }
 
     log::LOG
@@ -0,0 +1,82 @@
+use crate::log;
+use anyhow::Context;
+use std::time::UNIX_EPOCH;
+use winapi::um::shlobj::CSIDL_LOCAL_APPDATA;
+
+const DATA_DIR: &str = "Data";
+const PLUGINS_TXT: &str = "plugins.txt";
+// Creation Club plugins the game loads without listing them in plugins.txt
+const CCC_PATH: &str = "Skyrim.ccc";
+const IMPLICIT_MASTERS: [&str; 5] = [
+    "Skyrim.esm",
+    "Update.esm",
+    "Dawnguard.esm",
+    "HearthFires.esm",
+    "Dragonborn.esm",
+];
+
+/// Active plugins in load order.
+pub(crate) fn plugins() -> anyhow::Result<Vec<String>> {
+    let mut plugins: Vec<String> = IMPLICIT_MASTERS.iter().map(|p| p.to_string()).collect();
+
+    if let Ok(ccc) = std::fs::read_to_string(CCC_PATH) {
+        plugins.extend(
+            ccc.lines()
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .filter(|p| std::path::Path::new(DATA_DIR).join(p).exists())
+                .map(String::from),
+        );
+    }
+
+    let plugins_txt = format!(
+        "{}\\{}\\{}",
+        log::folder_path(CSIDL_LOCAL_APPDATA)?,
+        log::game_folder(),
+        PLUGINS_TXT
+    );
+    let plugins_txt = std::fs::read_to_string(&plugins_txt)
+        .with_context(|| format!("could not read {}", plugins_txt))?;
+    plugins.extend(
+        plugins_txt
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix('*'))
+            .map(String::from),
+    );
+
+    let mut seen = std::collections::HashSet::new();
+    plugins.retain(|p| seen.insert(p.to_ascii_lowercase()));
+    Ok(plugins)
+}
+
+// FNV-1a; unlike DefaultHasher it is stable across builds, which the file name relies on.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+        // separator, so "ab"+"c" and "a"+"bc" differ
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x100000001b3);
+    }
+}
+
+/// Identifies the load order together with each plugin's modification time and this plugin's
+/// version, so any change to what gets indexed gives a different hash.
+pub(crate) fn hash() -> anyhow::Result<u64> {
+    let mut hasher = Fnv1a(0xcbf29ce484222325);
+    hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+    for plugin in plugins()? {
+        hasher.write(plugin.to_ascii_lowercase().as_bytes());
+        let modified = std::fs::metadata(std::path::Path::new(DATA_DIR).join(&plugin))
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        hasher.write(&modified.to_le_bytes());
+    }
+    Ok(hasher.0)
+}
@@ -15,7 +15,11 @@ use winapi::um::shlobj::{
     SHGetFolderPathA, CSIDL_FLAG_CREATE, CSIDL_MYDOCUMENTS, SHGFP_TYPE_CURRENT,
 };
 
-const LOG_PATH: &str = "\\My Games\\Skyrim Special Edition\\SKSE\\skyrim-search-se.log";
+const GAME_FOLDER: &str = "Skyrim Special Edition";
+const GOG_GAME_FOLDER: &str = "Skyrim Special Edition GOG";
+// only shipped with the GOG release, next to SkyrimSE.exe
+const GOG_MARKER: &str = "Galaxy64.dll";
+const LOG_FILE: &str = "\\skyrim-search-se.log";
 
 lazy_static! {
     pub static ref LOG: Mutex<LineWriter<File>> = {
@@ -30,12 +34,13 @@ lazy_static! {
     };
 }
 
-fn open_log_file() -> anyhow::Result<LineWriter<File>> {
+/// SHGetFolderPathA, e.g. CSIDL_MYDOCUMENTS
+pub(crate) fn folder_path(csidl: i32) -> anyhow::Result<String> {
     unsafe {
         let mut path = Vec::with_capacity(MAX_PATH);
         let result = SHGetFolderPathA(
             NULL as HWND,
-            CSIDL_MYDOCUMENTS | CSIDL_FLAG_CREATE,
+            csidl | CSIDL_FLAG_CREATE,
             NULL,
             SHGFP_TYPE_CURRENT,
             path.as_mut_ptr(),
@@ -48,13 +53,35 @@ fn open_log_file() -> anyhow::Result<LineWriter<File>> {
             );
         }
 
-        let path = String::from(CStr::from_ptr(path.as_ptr()).to_str()?) + LOG_PATH;
+        Ok(String::from(CStr::from_ptr(path.as_ptr()).to_str()?))
+    }
+}
 
-        let file = File::create(&path)?;
-        Ok(LineWriter::new(file))
+/// Folder the game uses under My Games and local app data; the GOG release has its own.
+pub(crate) fn game_folder() -> &'static str {
+    if std::path::Path::new(GOG_MARKER).exists() {
+        GOG_GAME_FOLDER
+    } else {
+        GAME_FOLDER
     }
 }
 
+/// My Games\Skyrim Special Edition\SKSE, where SKSE keeps its logs
+pub(crate) fn skse_dir() -> anyhow::Result<String> {
+    Ok(format!(
+        "{}\\My Games\\{}\\SKSE",
+        folder_path(CSIDL_MYDOCUMENTS)?,
+        game_folder()
+    ))
+}
+
+fn open_log_file() -> anyhow::Result<LineWriter<File>> {
+    let path = skse_dir()? + LOG_FILE;
+
+    let file = File::create(&path)?;
+    Ok(LineWriter::new(file))
+}
+
 pub(crate) trait Loggable<T> {
     fn logging_ok(self) -> Option<T>;
 }
@@ -0,0 +1,200 @@
+use crate::api;
+use crate::console;
+use crate::db;
+use crate::db::TaskQueue;
+use crate::form;
+use crate::log::Loggable;
+use crate::{search, status};
+use crate::{PluginHandle, SKSEInterface};
+use anyhow::{anyhow, Context};
+use late_static::LateStatic;
+use lazy_static::lazy_static;
+use std::fmt;
+use std::sync::Mutex;
+use win_dbg_logger::output_debug_string;
+use winapi::ctypes::{c_char, c_void};
+
+const K_INTERFACE_MESSAGING: u32 = 5;
+
+const K_MESSAGE_POST_POST_LOAD: u32 = 1;
+const K_MESSAGE_PRE_LOAD_GAME: u32 = 2;
+const K_MESSAGE_POST_LOAD_GAME: u32 = 3;
+const K_MESSAGE_NEW_GAME: u32 = 7;
+const K_MESSAGE_DATA_LOADED: u32 = 8;
+
+#[allow(dead_code)]
+#[repr(C)]
+pub(crate) struct Message {
+    sender: *const c_char,
+    message_type: u32,
+    data_len: u32,
+    data: *mut c_void,
+}
+
+type EventCallback = extern "C" fn(*mut Message);
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SKSEMessagingInterface {
+    interface_version: u32,
+    register_listener: extern "C" fn(PluginHandle, *const c_char, EventCallback) -> bool,
+    dispatch: extern "C" fn(PluginHandle, u32, *mut c_void, u32, *const c_char) -> bool,
+    get_event_dispatcher: extern "C" fn(u32) -> *mut c_void,
+}
+
+/// What the current contents of the index were built from.
+#[derive(Debug, Clone)]
+pub(crate) enum IndexSource {
+    Startup,
+    DataLoaded,
+    NewGame,
+    Save { name: String, loaded: bool },
+}
+
+impl fmt::Display for IndexSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexSource::Startup => write!(f, "index built for game startup (data not loaded yet)"),
+            IndexSource::DataLoaded => write!(f, "index built for data load"),
+            IndexSource::NewGame => write!(f, "index built for new game"),
+            IndexSource::Save { name, loaded: true } => write!(f, "index built for save {}", name),
+            IndexSource::Save {
+                name,
+                loaded: false,
+            } => write!(f, "index building for save {} (loading)", name),
+        }
+    }
+}
+
+lazy_static! {
+    pub(crate) static ref INDEX_SOURCE: Mutex<IndexSource> = Mutex::new(IndexSource::Startup);
+}
+
+struct State {
+    messaging: *const SKSEMessagingInterface,
+    plugin_handle: PluginHandle,
+    task_queue: TaskQueue,
+}
+unsafe impl Sync for State {}
+static S: LateStatic<State> = LateStatic::new();
+
+fn set_index_source(source: IndexSource) {
+    output_debug_string(format!("index source: {}", source).as_str());
+    if let Ok(mut index_source) = INDEX_SOURCE.lock() {
+        *index_source = source;
+    }
+}
+
+// Refs in the actor table belong to the previous save
+fn clear_actors() -> anyhow::Result<()> {
+    S.task_queue
+        .send(Box::new(|db| {
+            db.execute_batch("DELETE FROM actor;")
+                .context("clear_actors execute")?;
+            Ok(())
+        }))
+        .map_err(|e| anyhow!(e.to_string()))
+}
+
+extern "C" fn on_message(msg: *mut Message) {
+    let msg = match unsafe { msg.as_ref() } {
+        Some(msg) => msg,
+        None => return,
+    };
+
+    match msg.message_type {
+        K_MESSAGE_POST_POST_LOAD => {
+            api::announce().context("api::announce").logging_ok();
+        }
+        K_MESSAGE_DATA_LOADED => {
+            if status::is_active("form::data_handler") {
+                form::data_handler::index_all()
+                    .context("index_all")
+                    .logging_ok();
+            }
+            S.task_queue
+                .send(Box::new(db::mark_complete))
+                .map_err(|e| anyhow!(e.to_string()))
+                .logging_ok();
+            set_index_source(IndexSource::DataLoaded);
+            console::print_pending();
+        }
+        K_MESSAGE_NEW_GAME => {
+            clear_actors().logging_ok();
+            set_index_source(IndexSource::NewGame);
+        }
+        K_MESSAGE_PRE_LOAD_GAME => {
+            let name = if msg.data.is_null() {
+                String::new()
+            } else {
+                let data = unsafe {
+                    std::slice::from_raw_parts(msg.data as *const u8, msg.data_len as usize)
+                };
+                String::from_utf8_lossy(data)
+                    .trim_end_matches('\0')
+                    .to_string()
+            };
+            clear_actors().logging_ok();
+            set_index_source(IndexSource::Save {
+                name,
+                loaded: false,
+            });
+        }
+        K_MESSAGE_POST_LOAD_GAME => {
+            let success = !msg.data.is_null();
+            if let Ok(mut index_source) = INDEX_SOURCE.lock() {
+                if let IndexSource::Save { loaded, .. } = &mut *index_source {
+                    *loaded = success;
+                }
+            }
+            if success {
+                search::quest_stage_text_job()
+                    .and_then(|job| S.task_queue.send(job).map_err(|e| anyhow!(e.to_string())))
+                    .context("quest_stage_text_job")
+                    .logging_ok();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Sends a message from this plugin to every listener.
+pub(crate) fn dispatch(message_type: u32, data: *mut c_void, data_len: u32) -> anyhow::Result<()> {
+    let messaging = unsafe { &*S.messaging };
+    if !(messaging.dispatch)(
+        S.plugin_handle,
+        message_type,
+        data,
+        data_len,
+        std::ptr::null(),
+    ) {
+        anyhow::bail!("Dispatch({:#x}) failed", message_type);
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn init(skse: &SKSEInterface) -> anyhow::Result<()> {
+    let messaging = (skse.query_interface)(K_INTERFACE_MESSAGING) as *const SKSEMessagingInterface;
+    if messaging.is_null() {
+        anyhow::bail!("query_interface(kInterface_Messaging) returned null");
+    }
+
+    LateStatic::assign(
+        &S,
+        State {
+            messaging,
+            plugin_handle: (skse.get_plugin_handle)(),
+            task_queue: db::TASK_QUEUE.lock().unwrap().clone(),
+        },
+    );
+
+    if !((*S.messaging).register_listener)(
+        S.plugin_handle,
+        "SKSE\0".as_ptr() as *const c_char,
+        on_message,
+    ) {
+        anyhow::bail!("RegisterListener failed");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,535 @@
+//! Papyrus natives of the `SkyrimSearch` script, see Source/Scripts/SkyrimSearch.psc. They run
+//! the same queries as the console commands; the script wraps them into the Actor/Cell/Quest
+//! API, so only String, Int and Int[] cross the boundary here.
+
+use crate::db;
+use crate::log::Loggable;
+use crate::search;
+use crate::SKSEInterface;
+use anyhow::{anyhow, Context};
+use late_static::LateStatic;
+use rusqlite::types::Null;
+use rusqlite::{params, ToSql};
+use skyrim_search_index::fuzzy;
+use std::ffi::{CStr, CString};
+use std::mem::transmute;
+use std::ops::Deref;
+use std::ptr;
+use win_dbg_logger::output_debug_string;
+use winapi::ctypes::{c_char, c_void};
+
+const K_INTERFACE_PAPYRUS: u32 = 2;
+
+const SCRIPT_NAME: &str = "SkyrimSearch";
+
+// Papyrus arrays hold at most 128 elements.
+const MAX_ARRAY_SIZE: usize = 128;
+
+// RE::BSScript::TypeInfo::RawType
+const TYPE_NONE: u64 = 0;
+const TYPE_STRING: u64 = 2;
+const TYPE_INT: u64 = 3;
+const TYPE_INT_ARRAY: u64 = 13;
+
+// RE::BSScript::IFunction::CallResult
+const CALL_COMPLETED: u32 = 0;
+
+// RE::BSScript::IVirtualMachine vtable slots
+const VM_CREATE_ARRAY: usize = 0x16;
+const VM_BIND_NATIVE_METHOD: usize = 0x18;
+
+type RegisterFunctions = extern "C" fn(*mut c_void) -> bool;
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SKSEPapyrusInterface {
+    interface_version: u32,
+    register: extern "C" fn(RegisterFunctions) -> bool,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union Value {
+    int: i32,
+    string: *const c_char,
+    array: *mut Array,
+    raw: u64,
+}
+
+#[repr(C)]
+struct Variable {
+    type_: u64,   // 00
+    value: Value, // 08
+}
+
+#[repr(C)]
+struct Stack {
+    unk00: [u8; 0x48],      // 00
+    top: *const c_void,     // 48 StackFrame*
+    unk50: u64,             // 50
+    return_value: Variable, // 58
+}
+
+#[repr(C)]
+struct Array {
+    ref_count: u32,     // 00
+    pad04: u32,         // 04
+    element_type: u64,  // 08
+    size: u32,          // 10
+    pad14: u32,         // 14
+    lock: u64,          // 18
+    elements: Variable, // 20, `size` of them
+}
+
+struct State {
+    get_entry: extern "C" fn(*mut *const c_char, *const c_char, bool),
+    release: extern "C" fn(*mut *const c_char),
+    get_page_for_frame: extern "C" fn(*mut Stack, *const c_void) -> u32,
+    get_stack_frame_variable: extern "C" fn(*mut Stack, *const c_void, u32, u32) -> *mut Variable,
+}
+unsafe impl Sync for State {}
+static S: LateStatic<State> = LateStatic::new();
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("get_entry", &(self.get_entry as usize))
+            .field("release", &(self.release as usize))
+            .field("get_page_for_frame", &(self.get_page_for_frame as usize))
+            .field(
+                "get_stack_frame_variable",
+                &(self.get_stack_frame_variable as usize),
+            )
+            .finish()
+    }
+}
+
+// BSFixedString: a pooled string, NULL for ""
+fn pooled(s: &str) -> *const c_char {
+    let s = CString::new(s.replace('\0', "")).unwrap_or_default();
+    let mut entry = ptr::null();
+    (S.get_entry)(&mut entry, s.as_ptr(), false);
+    entry
+}
+
+fn release(entry: &mut *const c_char) {
+    if !entry.is_null() {
+        (S.release)(entry);
+        *entry = ptr::null();
+    }
+}
+
+enum Return {
+    Int(i32),
+    String(String),
+    IntArray(Vec<i32>),
+}
+
+struct Native {
+    name: &'static str,
+    params: &'static [(&'static str, u64)],
+    return_type: u64,
+    call: fn(&[&Variable]) -> anyhow::Result<Return>,
+}
+
+static NATIVES: [Native; 3] = [
+    Native {
+        name: "FindNpcRefIds",
+        params: &[("asName", TYPE_STRING)],
+        return_type: TYPE_INT_ARRAY,
+        call: find_npc_ref_ids,
+    },
+    Native {
+        name: "FindCellId",
+        params: &[("asName", TYPE_STRING)],
+        return_type: TYPE_INT,
+        call: find_cell_id,
+    },
+    Native {
+        name: "GetQuestStageLogById",
+        params: &[("aiQuestFormId", TYPE_INT), ("aiStage", TYPE_INT)],
+        return_type: TYPE_STRING,
+        call: get_quest_stage_log_by_id,
+    },
+];
+
+fn string_arg(arg: &Variable) -> anyhow::Result<String> {
+    if arg.type_ != TYPE_STRING {
+        anyhow::bail!("expected a String argument, got type {}", arg.type_);
+    }
+    let s = unsafe { arg.value.string };
+    if s.is_null() {
+        return Ok(String::new());
+    }
+    Ok(unsafe { CStr::from_ptr(s) }.to_string_lossy().to_string())
+}
+
+fn int_arg(arg: &Variable) -> anyhow::Result<i32> {
+    if arg.type_ != TYPE_INT {
+        anyhow::bail!("expected an Int argument, got type {}", arg.type_);
+    }
+    Ok(unsafe { arg.value.int })
+}
+
+// Distinct non-NULL form ids of `column` in the rows `sql` finds, like the console command: full
+// text matches, or the closest fuzzy matches if there are none.
+fn find(sql: &str, fuzzy_sql: &str, column: &str, query: &str) -> anyhow::Result<Vec<u32>> {
+    fn collect(
+        stmt: &mut rusqlite::Statement,
+        params: &[&dyn ToSql],
+        column: &str,
+    ) -> anyhow::Result<Vec<u32>> {
+        let mut ids = Vec::new();
+        let mut rows = stmt.query(params).context("query error")?;
+        while let Some(row) = rows.next().context("rows.next() error")? {
+            if let Some(id) = row.get::<_, Option<i64>>(column)? {
+                let id = id as u32;
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+            if ids.len() == MAX_ARRAY_SIZE {
+                break;
+            }
+        }
+        Ok(ids)
+    }
+
+    let db = db::READER.lock().unwrap();
+    if let Some(fts_query) = search::fts_query(query) {
+        let mut stmt = db.prepare_cached(sql).context("prepare error")?;
+        let ids = collect(&mut stmt, params![fts_query, Null, Null], column)?;
+        if !ids.is_empty() {
+            return Ok(ids);
+        }
+    }
+    let mut stmt = db.prepare_cached(fuzzy_sql).context("prepare error")?;
+    collect(&mut stmt, params![query, fuzzy::MIN_SCORE, Null], column)
+}
+
+// Loaded refs of the npcs `ss npc` finds; npcs without one are left out.
+fn find_npc_ref_ids(args: &[&Variable]) -> anyhow::Result<Return> {
+    let name = string_arg(args[0])?;
+    let ids = find(search::NPC_SQL, search::NPC_FUZZY_SQL, "ref_id", &name)?;
+    Ok(Return::IntArray(
+        ids.into_iter().map(|id| id as i32).collect(),
+    ))
+}
+
+// The best match of `ss cell`, or 0.
+fn find_cell_id(args: &[&Variable]) -> anyhow::Result<Return> {
+    let name = string_arg(args[0])?;
+    let ids = find(search::CELL_SQL, search::CELL_FUZZY_SQL, "form_id", &name)?;
+    Ok(Return::Int(ids.first().map_or(0, |&id| id as i32)))
+}
+
+fn get_quest_stage_log_by_id(args: &[&Variable]) -> anyhow::Result<Return> {
+    let form_id = int_arg(args[0])? as u32;
+    let stage = int_arg(args[1])?;
+    if !(0..=u16::MAX as i32).contains(&stage) {
+        anyhow::bail!("invalid stage {}", stage);
+    }
+    Ok(Return::String(search::quest_stage_log(
+        form_id,
+        stage as u16,
+    )?))
+}
+
+// RE::BSScript::IFunction vtable
+#[allow(dead_code)]
+#[repr(C)]
+struct FunctionVtbl {
+    destructor: extern "C" fn(*mut Function, u32) -> *mut Function, // 00
+    get_name: extern "C" fn(&Function) -> *const *const c_char,     // 01
+    get_object_type_name: extern "C" fn(&Function) -> *const *const c_char, // 02
+    get_state_name: extern "C" fn(&Function) -> *const *const c_char, // 03
+    get_return_type: extern "C" fn(&Function, *mut u64) -> *mut u64, // 04
+    get_param_count: extern "C" fn(&Function) -> u32,               // 05
+    get_param: extern "C" fn(&Function, u32, *mut *const c_char, *mut u64), // 06
+    get_stack_frame_size: extern "C" fn(&Function) -> u32,          // 07
+    get_is_native: extern "C" fn(&Function) -> bool,                // 08
+    get_is_static: extern "C" fn(&Function) -> bool,                // 09
+    get_is_empty: extern "C" fn(&Function) -> bool,                 // 0A
+    get_function_type: extern "C" fn(&Function) -> u32,             // 0B
+    get_user_flags: extern "C" fn(&Function) -> u32,                // 0C
+    get_doc_string: extern "C" fn(&Function) -> *const *const c_char, // 0D
+    insert_locals: extern "C" fn(&Function, *mut c_void),           // 0E
+    call: extern "C" fn(&Function, *const *mut Stack, *mut c_void, *mut c_void, bool) -> u32, // 0F
+    get_source_filename: extern "C" fn(&Function) -> *const *const c_char, // 10
+    translate_ip_to_line_number: extern "C" fn(&Function, u32, *mut u32) -> bool, // 11
+    get_var_name_for_stack_index: extern "C" fn(&Function, u32, *mut *const c_char) -> bool, // 12
+    can_be_called_from_tasklets: extern "C" fn(&Function) -> bool,  // 13
+    set_callable_from_tasklets: extern "C" fn(&mut Function, bool), // 14
+}
+
+// An IFunction bound by the VM, which owns it through the intrusive ref count.
+#[repr(C)]
+struct Function {
+    vtable: &'static FunctionVtbl, // 00
+    ref_count: u32,                // 08 BSIntrusiveRefCounted
+    callable_from_tasklets: bool,  // 0C
+    name: *const c_char,
+    object_type_name: *const c_char,
+    state_name: *const c_char,
+    doc_string: *const c_char,
+    source_filename: *const c_char,
+    native: &'static Native,
+}
+
+static FUNCTION_VTBL: FunctionVtbl = FunctionVtbl {
+    destructor: Function::destructor,
+    get_name: Function::get_name,
+    get_object_type_name: Function::get_object_type_name,
+    get_state_name: Function::get_state_name,
+    get_return_type: Function::get_return_type,
+    get_param_count: Function::get_param_count,
+    get_param: Function::get_param,
+    get_stack_frame_size: Function::get_param_count,
+    get_is_native: Function::yes,
+    get_is_static: Function::yes,
+    get_is_empty: Function::no,
+    get_function_type: Function::zero,
+    get_user_flags: Function::zero,
+    get_doc_string: Function::get_doc_string,
+    insert_locals: Function::insert_locals,
+    call: Function::call,
+    get_source_filename: Function::get_source_filename,
+    translate_ip_to_line_number: Function::translate_ip_to_line_number,
+    get_var_name_for_stack_index: Function::get_var_name_for_stack_index,
+    can_be_called_from_tasklets: Function::can_be_called_from_tasklets,
+    set_callable_from_tasklets: Function::set_callable_from_tasklets,
+};
+
+impl Function {
+    fn new(native: &'static Native) -> Self {
+        Function {
+            vtable: &FUNCTION_VTBL,
+            ref_count: 0,
+            // the index reader and the quest forms are not meant for tasklet threads
+            callable_from_tasklets: false,
+            name: pooled(native.name),
+            object_type_name: pooled(SCRIPT_NAME),
+            state_name: ptr::null(),
+            doc_string: ptr::null(),
+            source_filename: pooled("<native>"),
+            native,
+        }
+    }
+
+    extern "C" fn destructor(this: *mut Function, flags: u32) -> *mut Function {
+        unsafe {
+            release(&mut (*this).name);
+            release(&mut (*this).object_type_name);
+            release(&mut (*this).source_filename);
+            if flags & 1 != 0 {
+                drop(Box::from_raw(this));
+            }
+        }
+        this
+    }
+
+    extern "C" fn get_name(&self) -> *const *const c_char {
+        &self.name
+    }
+
+    extern "C" fn get_object_type_name(&self) -> *const *const c_char {
+        &self.object_type_name
+    }
+
+    extern "C" fn get_state_name(&self) -> *const *const c_char {
+        &self.state_name
+    }
+
+    extern "C" fn get_doc_string(&self) -> *const *const c_char {
+        &self.doc_string
+    }
+
+    extern "C" fn get_source_filename(&self) -> *const *const c_char {
+        &self.source_filename
+    }
+
+    extern "C" fn get_param_count(&self) -> u32 {
+        self.native.params.len() as u32
+    }
+
+    // natives are static, have no locals and a normal function type, i.e. 0, as do user flags
+    extern "C" fn yes(&self) -> bool {
+        true
+    }
+
+    extern "C" fn no(&self) -> bool {
+        false
+    }
+
+    extern "C" fn zero(&self) -> u32 {
+        0
+    }
+
+    extern "C" fn insert_locals(&self, _frame: *mut c_void) {}
+
+    extern "C" fn can_be_called_from_tasklets(&self) -> bool {
+        self.callable_from_tasklets
+    }
+
+    extern "C" fn set_callable_from_tasklets(&mut self, callable: bool) {
+        self.callable_from_tasklets = callable;
+    }
+
+    extern "C" fn get_return_type(&self, out: *mut u64) -> *mut u64 {
+        unsafe { *out = self.native.return_type };
+        out
+    }
+
+    extern "C" fn get_param(&self, index: u32, name: *mut *const c_char, type_: *mut u64) {
+        let (param_name, param_type) = self
+            .native
+            .params
+            .get(index as usize)
+            .copied()
+            .unwrap_or(("", TYPE_NONE));
+        unsafe {
+            release(&mut *name);
+            *name = pooled(param_name);
+            *type_ = param_type;
+        }
+    }
+
+    extern "C" fn translate_ip_to_line_number(&self, _ip: u32, line: *mut u32) -> bool {
+        unsafe { *line = 0 };
+        false
+    }
+
+    extern "C" fn get_var_name_for_stack_index(
+        &self,
+        index: u32,
+        name: *mut *const c_char,
+    ) -> bool {
+        match self.native.params.get(index as usize) {
+            Some((param_name, _)) => {
+                unsafe {
+                    release(&mut *name);
+                    *name = pooled(param_name);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    extern "C" fn call(
+        &self,
+        stack: *const *mut Stack,
+        _logger: *mut c_void,
+        vm: *mut c_void,
+        _arg4: bool,
+    ) -> u32 {
+        let stack = unsafe { &mut **stack };
+        // A failed call returns the default of the return type (0, "" or a None array) rather
+        // than aborting the calling script.
+        let value = unsafe { self.invoke(stack) }
+            .with_context(|| format!("{}.{}", SCRIPT_NAME, self.native.name))
+            .logging_ok();
+        unsafe {
+            set_return(&mut stack.return_value, self.native.return_type, value, vm)
+                .context("set return value")
+                .logging_ok();
+        }
+        CALL_COMPLETED
+    }
+
+    unsafe fn invoke(&self, stack: &mut Stack) -> anyhow::Result<Return> {
+        let frame = stack.top;
+        if frame.is_null() {
+            anyhow::bail!("no stack frame");
+        }
+        let page = (S.get_page_for_frame)(stack, frame);
+        let args = (0..self.native.params.len())
+            .map(|i| {
+                (S.get_stack_frame_variable)(stack, frame, i as u32, page)
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("no argument {}", i))
+            })
+            .collect::<anyhow::Result<Vec<&Variable>>>()?;
+        (self.native.call)(&args)
+    }
+}
+
+unsafe fn set_return(
+    var: &mut Variable,
+    return_type: u64,
+    value: Option<Return>,
+    vm: *mut c_void,
+) -> anyhow::Result<()> {
+    // The slot holds no value yet when a native is called.
+    var.type_ = return_type;
+    var.value = Value { raw: 0 };
+    match value {
+        None => {}
+        Some(Return::Int(v)) => var.value.int = v,
+        Some(Return::String(s)) => var.value.string = pooled(&s),
+        Some(Return::IntArray(v)) => {
+            let create_array: extern "C" fn(*mut c_void, *const u64, u32, *mut *mut Array) -> bool =
+                transmute(*(*(vm as *const *const usize)).add(VM_CREATE_ARRAY));
+            let mut array = ptr::null_mut();
+            if !create_array(vm, &TYPE_INT, v.len() as u32, &mut array) || array.is_null() {
+                anyhow::bail!("CreateArray({}) failed", v.len());
+            }
+            let elements = &mut (*array).elements as *mut Variable;
+            for (i, v) in v.into_iter().enumerate() {
+                let element = &mut *elements.add(i);
+                element.type_ = TYPE_INT;
+                element.value.int = v;
+            }
+            // the returned smart pointer's reference moves into the return value
+            var.value.array = array;
+        }
+    }
+    Ok(())
+}
+
+extern "C" fn register(vm: *mut c_void) -> bool {
+    if vm.is_null() {
+        return false;
+    }
+    let bind_native_method: extern "C" fn(*mut c_void, *mut Function) -> bool =
+        unsafe { transmute(*(*(vm as *const *const usize)).add(VM_BIND_NATIVE_METHOD)) };
+    NATIVES
+        .iter()
+        .map(|native| {
+            let function = Box::into_raw(Box::new(Function::new(native)));
+            if !bind_native_method(vm, function) {
+                anyhow::bail!("BindNativeMethod({}.{}) failed", SCRIPT_NAME, native.name);
+            }
+            Ok(())
+        })
+        .filter_map(|result| result.logging_ok())
+        .count()
+        == NATIVES.len()
+}
+
+pub(crate) unsafe fn init(skse: &SKSEInterface, image_base: usize) -> anyhow::Result<()> {
+    let papyrus = (skse.query_interface)(K_INTERFACE_PAPYRUS) as *const SKSEPapyrusInterface;
+    let papyrus = papyrus
+        .as_ref()
+        .ok_or_else(|| anyhow!("query_interface(kInterface_Papyrus) returned null"))?;
+
+    LateStatic::assign(
+        &S,
+        State {
+            get_entry: transmute(image_base + versionlib!(BSStringPool_GetEntry)?),
+            release: transmute(image_base + versionlib!(BSStringPool_Release)?),
+            get_page_for_frame: transmute(image_base + versionlib!(Stack_GetPageForFrame)?),
+            get_stack_frame_variable: transmute(
+                image_base + versionlib!(Stack_GetStackFrameVariable)?,
+            ),
+        },
+    );
+
+    output_debug_string(format!("S: {:#x?}", S.deref()).as_str());
+
+    if !(papyrus.register)(register) {
+        anyhow::bail!("Register failed");
+    }
+
+    Ok(())
+}
@@ -14,3 +14,10 @@ pub(crate) unsafe fn patch_bytes<T, U>(
 
     Ok(original_bytes)
 }
+
+// Puts back what `patch_bytes` returned, for inits that fail after patching.
+pub(crate) unsafe fn restore_bytes<U>(original_bytes: &[u8], dst: *mut U) -> anyhow::Result<()> {
+    patch_bytes(original_bytes.as_ptr(), dst, original_bytes.len())
+        .context("restore_bytes")
+        .map(|_| ())
+}
@@ -0,0 +1,76 @@
+use crate::db;
+use crate::db::Job;
+use crate::form::qust::TESQuest;
+use crate::form::TESForm;
+use crate::status;
+use anyhow::{anyhow, Context};
+use rusqlite::{params, NO_PARAMS};
+
+// The queries behind the console commands live in the index crate, which tests them.
+pub(crate) use skyrim_search_index::search::{
+    fts_query, CELL_BY_ID_SQL, CELL_FUZZY_SQL, CELL_SQL, NPC_BY_ID_SQL, NPC_FUZZY_SQL, NPC_SQL,
+    QUEST_BY_ID_SQL, QUEST_FUZZY_SQL, QUEST_SQL, QUEST_STAGE_BY_ID_SQL, QUEST_STAGE_FUZZY_SQL,
+    QUEST_STAGE_SQL,
+};
+
+/// Resolves the journal text of every stage that does not have it yet, so quest_stage_fts can
+/// find stages by their text. Needs a loaded save; the returned job stores the text.
+pub(crate) fn quest_stage_text_job() -> anyhow::Result<Job> {
+    let stages: Vec<(i64, i64)> = {
+        let db = db::READER.lock().unwrap();
+        let mut stmt = db
+            // quest_stage.form_id was TEXT before schema version 2
+            .prepare_cached(
+                "SELECT CAST(form_id AS INTEGER), stage FROM quest_stage WHERE text IS NULL",
+            )
+            .context("prepare error")?;
+        let stages = stmt
+            .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))
+            .context("query error")?
+            .collect::<rusqlite::Result<_>>()?;
+        stages
+    };
+    let texts: Vec<(i64, i64, String)> = stages
+        .into_iter()
+        .filter_map(|(form_id, stage)| {
+            let text = quest_stage_log(form_id as u32, stage as u16).ok()?;
+            if text.is_empty() {
+                return None;
+            }
+            Some((form_id, stage, text))
+        })
+        .collect();
+
+    Ok(Box::new(move |db| {
+        for (form_id, stage, text) in texts {
+            db.prepare_cached("UPDATE quest_stage SET text = ? WHERE form_id = ? AND stage = ?")
+                .context("quest_stage_text prepare")?
+                .execute(params![text, form_id, stage])
+                .context("quest_stage_text execute")?;
+        }
+        Ok(())
+    }))
+}
+
+/// Journal text of `stage`; only available once a save has been loaded.
+pub(crate) fn quest_stage_log(quest_form_id: u32, stage: u16) -> anyhow::Result<String> {
+    if !status::is_active("form::qust") {
+        anyhow::bail!("quest hooks are disabled, see ss status");
+    }
+    let quest = TESForm::look_up_by_id(quest_form_id) as *const TESQuest;
+    if quest.is_null() {
+        anyhow::bail!("no quest {:08X}", quest_form_id);
+    }
+    let quest: &TESQuest = unsafe { &*quest };
+    let index = quest
+        .get_log(stage)
+        .ok_or_else(|| anyhow!("invalid data"))?;
+    let log_entry = if let Some(log_entry) = index.head.into_iter().next() {
+        quest
+            .get_log_description(unsafe { &*log_entry })
+            .to_string()
+    } else {
+        String::new()
+    };
+    Ok(log_entry)
+}
@@ -0,0 +1,329 @@
+use crate::db;
+use crate::db::TaskQueue;
+use crate::log::Loggable;
+use crate::{PluginHandle, SKSEInterface};
+use anyhow::{anyhow, Context};
+use late_static::LateStatic;
+use rusqlite::{params, NO_PARAMS};
+use skyrim_search_index::IndexEvent;
+use win_dbg_logger::output_debug_string;
+use winapi::ctypes::c_void;
+
+const K_INTERFACE_SERIALIZATION: u32 = 3;
+
+const fn four_cc(s: &[u8; 4]) -> u32 {
+    ((s[0] as u32) << 24) | ((s[1] as u32) << 16) | ((s[2] as u32) << 8) | (s[3] as u32)
+}
+
+const UNIQUE_ID: u32 = four_cc(b"SSSE");
+const RECORD_HISTORY: u32 = four_cc(b"HIST");
+const RECORD_BOOKMARKS: u32 = four_cc(b"BKMK");
+const RECORD_REFS: u32 = four_cc(b"REFS");
+const RECORD_VERSION: u32 = 1;
+
+type EventCallback = extern "C" fn(*const SKSESerializationInterface);
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SKSESerializationInterface {
+    version: u32,
+    set_unique_id: extern "C" fn(PluginHandle, u32),
+    set_revert_callback: extern "C" fn(PluginHandle, EventCallback),
+    set_save_callback: extern "C" fn(PluginHandle, EventCallback),
+    set_load_callback: extern "C" fn(PluginHandle, EventCallback),
+    set_form_delete_callback: extern "C" fn(PluginHandle, extern "C" fn(u64)),
+    write_record: extern "C" fn(u32, u32, *const c_void, u32) -> bool,
+    open_record: extern "C" fn(u32, u32) -> bool,
+    write_record_data: extern "C" fn(*const c_void, u32) -> bool,
+    get_next_record_info: extern "C" fn(*mut u32, *mut u32, *mut u32) -> bool,
+    read_record_data: extern "C" fn(*mut c_void, u32) -> u32,
+    resolve_handle: extern "C" fn(u64, *mut u64) -> bool,
+    resolve_form_id: extern "C" fn(u32, *mut u32) -> bool,
+}
+
+struct State {
+    task_queue: TaskQueue,
+}
+unsafe impl Sync for State {}
+static S: LateStatic<State> = LateStatic::new();
+
+/// Per-save state kept in the co-save.
+#[derive(Debug, Default)]
+struct SaveData {
+    history: Vec<String>,
+    bookmarks: Vec<(u32, Option<String>)>,
+    refs: Vec<(u32, u32)>,
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn string(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.0.extend_from_slice(s.as_bytes());
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        if self.0.len() < 4 {
+            anyhow::bail!("record is truncated");
+        }
+        let (v, rest) = self.0.split_at(4);
+        self.0 = rest;
+        Ok(u32::from_le_bytes(v.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.u32()? as usize;
+        if self.0.len() < len {
+            anyhow::bail!("record is truncated");
+        }
+        let (v, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(String::from_utf8_lossy(v).to_string())
+    }
+}
+
+impl SaveData {
+    fn query(db: &rusqlite::Connection) -> anyhow::Result<Self> {
+        let history = db
+            .prepare_cached("SELECT command FROM history ORDER BY id")
+            .context("history prepare")?
+            .query_map(NO_PARAMS, |row| row.get(0))
+            .context("history query")?
+            .collect::<rusqlite::Result<_>>()?;
+        let bookmarks = db
+            .prepare_cached("SELECT form_id, note FROM bookmark ORDER BY form_id")
+            .context("bookmark prepare")?
+            .query_map(NO_PARAMS, |row| {
+                Ok((row.get::<_, i64>(0)? as u32, row.get(1)?))
+            })
+            .context("bookmark query")?
+            .collect::<rusqlite::Result<_>>()?;
+        let refs = db
+            .prepare_cached("SELECT form_id, base_form_id FROM actor ORDER BY form_id")
+            .context("actor prepare")?
+            .query_map(NO_PARAMS, |row| {
+                Ok((row.get::<_, i64>(0)? as u32, row.get::<_, i64>(1)? as u32))
+            })
+            .context("actor query")?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(SaveData {
+            history,
+            bookmarks,
+            refs,
+        })
+    }
+
+    // Refs are merged by `load` instead: the ACHR hooks may already have indexed refs of this
+    // save. Clearing them is left to `on_revert`.
+    fn replace(self, db: &rusqlite::Connection) -> anyhow::Result<()> {
+        db.execute_batch("DELETE FROM history; DELETE FROM bookmark;")
+            .context("replace execute")?;
+        for command in self.history {
+            db.prepare_cached("INSERT INTO history (command) VALUES (?)")
+                .context("history prepare")?
+                .execute(params![command])
+                .context("history execute")?;
+        }
+        for (form_id, note) in self.bookmarks {
+            db.prepare_cached("INSERT OR REPLACE INTO bookmark (form_id, note) VALUES (?, ?)")
+                .context("bookmark prepare")?
+                .execute(params![form_id, note])
+                .context("bookmark execute")?;
+        }
+        Ok(())
+    }
+}
+
+fn clear(db: &rusqlite::Connection) -> anyhow::Result<()> {
+    db.execute_batch("DELETE FROM history; DELETE FROM bookmark; DELETE FROM actor;")
+        .context("clear execute")?;
+    Ok(())
+}
+
+fn write_record(intfc: &SKSESerializationInterface, record: u32, data: Writer) -> bool {
+    (intfc.write_record)(
+        record,
+        RECORD_VERSION,
+        data.0.as_ptr() as *const c_void,
+        data.0.len() as u32,
+    )
+}
+
+fn save(intfc: &SKSESerializationInterface) -> anyhow::Result<()> {
+    // queried by the worker after everything queued before, e.g. a bookmark added just now
+    let (tx, rx) = std::sync::mpsc::channel();
+    S.task_queue
+        .send(Box::new(move |db| {
+            tx.send(SaveData::query(db)).ok();
+            Ok(())
+        }))
+        .map_err(|e| anyhow!(e.to_string()))?;
+    let save_data = rx.recv().context("worker stopped")??;
+
+    let mut history = Writer(Vec::new());
+    history.u32(save_data.history.len() as u32);
+    for command in save_data.history.iter() {
+        history.string(command);
+    }
+
+    let mut bookmarks = Writer(Vec::new());
+    bookmarks.u32(save_data.bookmarks.len() as u32);
+    for (form_id, note) in save_data.bookmarks.iter() {
+        bookmarks.u32(*form_id);
+        bookmarks.string(note.as_deref().unwrap_or(""));
+    }
+
+    let mut refs = Writer(Vec::new());
+    refs.u32(save_data.refs.len() as u32);
+    for (form_id, base_form_id) in save_data.refs.iter() {
+        refs.u32(*form_id);
+        refs.u32(*base_form_id);
+    }
+
+    if !write_record(intfc, RECORD_HISTORY, history)
+        || !write_record(intfc, RECORD_BOOKMARKS, bookmarks)
+        || !write_record(intfc, RECORD_REFS, refs)
+    {
+        anyhow::bail!("WriteRecord failed");
+    }
+
+    output_debug_string(
+        format!(
+            "saved {} history, {} bookmarks, {} refs",
+            save_data.history.len(),
+            save_data.bookmarks.len(),
+            save_data.refs.len()
+        )
+        .as_str(),
+    );
+
+    Ok(())
+}
+
+// Form ids change with the load order, so every id read from the co-save is resolved again.
+fn resolve_form_id(intfc: &SKSESerializationInterface, form_id: u32) -> Option<u32> {
+    let mut resolved = 0;
+    if (intfc.resolve_form_id)(form_id, &mut resolved) {
+        Some(resolved)
+    } else {
+        None
+    }
+}
+
+fn load(intfc: &SKSESerializationInterface) -> anyhow::Result<()> {
+    let mut save_data = SaveData::default();
+    let (mut record, mut version, mut length) = (0, 0, 0);
+
+    while (intfc.get_next_record_info)(&mut record, &mut version, &mut length) {
+        let mut buf = vec![0u8; length as usize];
+        let read = (intfc.read_record_data)(buf.as_mut_ptr() as *mut c_void, length);
+        if read != length {
+            anyhow::bail!("read {} of {} bytes of record {:#x}", read, length, record);
+        }
+        if version != RECORD_VERSION {
+            output_debug_string(
+                format!("skipping record {:#x} version {}", record, version).as_str(),
+            );
+            continue;
+        }
+
+        let mut r = Reader(&buf);
+        match record {
+            RECORD_HISTORY => {
+                for _ in 0..r.u32()? {
+                    save_data.history.push(r.string()?);
+                }
+            }
+            RECORD_BOOKMARKS => {
+                for _ in 0..r.u32()? {
+                    let form_id = r.u32()?;
+                    let note = r.string()?;
+                    if let Some(form_id) = resolve_form_id(intfc, form_id) {
+                        save_data
+                            .bookmarks
+                            .push((form_id, Some(note).filter(|note| !note.is_empty())));
+                    }
+                }
+            }
+            RECORD_REFS => {
+                for _ in 0..r.u32()? {
+                    let form_id = r.u32()?;
+                    let base_form_id = r.u32()?;
+                    if let (Some(form_id), Some(base_form_id)) = (
+                        resolve_form_id(intfc, form_id),
+                        resolve_form_id(intfc, base_form_id),
+                    ) {
+                        save_data.refs.push((form_id, base_form_id));
+                    }
+                }
+            }
+            _ => output_debug_string(format!("unknown record {:#x}", record).as_str()),
+        }
+    }
+
+    let refs = std::mem::take(&mut save_data.refs);
+    S.task_queue
+        .send(Box::new(move |db| save_data.replace(db)))
+        .map_err(|e| anyhow!(e.to_string()))?;
+    // merged with the refs the hooks indexed for this save
+    for (form_id, base_form_id) in refs {
+        S.task_queue
+            .index(IndexEvent::ActorRef {
+                form_id,
+                base_form_id,
+            })
+            .map_err(|e| anyhow!(e.to_string()))?;
+    }
+    Ok(())
+}
+
+extern "C" fn on_save(intfc: *const SKSESerializationInterface) {
+    if let Some(intfc) = unsafe { intfc.as_ref() } {
+        save(intfc).context("serialization save").logging_ok();
+    }
+}
+
+extern "C" fn on_load(intfc: *const SKSESerializationInterface) {
+    if let Some(intfc) = unsafe { intfc.as_ref() } {
+        load(intfc).context("serialization load").logging_ok();
+    }
+}
+
+extern "C" fn on_revert(_intfc: *const SKSESerializationInterface) {
+    S.task_queue
+        .send(Box::new(clear))
+        .map_err(|e| anyhow!(e.to_string()))
+        .logging_ok();
+}
+
+pub(crate) unsafe fn init(skse: &SKSEInterface) -> anyhow::Result<()> {
+    let serialization =
+        (skse.query_interface)(K_INTERFACE_SERIALIZATION) as *const SKSESerializationInterface;
+    let serialization = serialization
+        .as_ref()
+        .ok_or_else(|| anyhow!("query_interface(kInterface_Serialization) returned null"))?;
+
+    LateStatic::assign(
+        &S,
+        State {
+            task_queue: db::TASK_QUEUE.lock().unwrap().clone(),
+        },
+    );
+
+    let plugin_handle = (skse.get_plugin_handle)();
+    (serialization.set_unique_id)(plugin_handle, UNIQUE_ID);
+    (serialization.set_revert_callback)(plugin_handle, on_revert);
+    (serialization.set_save_callback)(plugin_handle, on_save);
+    (serialization.set_load_callback)(plugin_handle, on_load);
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use crate::log;
+use lazy_static::lazy_static;
+use std::io::Write;
+use std::sync::Mutex;
+use win_dbg_logger::output_debug_string;
+
+#[derive(Debug, Clone)]
+pub(crate) enum Status {
+    Active,
+    Failed(String),
+    Skipped(&'static str),
+}
+
+lazy_static! {
+    static ref STATUS: Mutex<Vec<(&'static str, Status)>> = Mutex::new(Vec::new());
+}
+
+fn set(name: &'static str, status: Status) {
+    let mut statuses = STATUS.lock().unwrap();
+    match statuses.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, s)) => *s = status,
+        None => statuses.push((name, status)),
+    }
+}
+
+pub(crate) fn is_active(name: &str) -> bool {
+    STATUS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|(n, status)| *n == name && matches!(status, Status::Active))
+}
+
+/// Runs one subsystem's init unless a subsystem it `requires` is not active, and records the
+/// outcome. Failing inits are expected to have undone their own patches.
+pub(crate) fn init<F>(name: &'static str, requires: &[&'static str], f: F) -> bool
+where
+    F: FnOnce() -> anyhow::Result<()>,
+{
+    if let Some(missing) = requires.iter().find(|&&r| !is_active(r)) {
+        set(name, Status::Skipped(missing));
+        return false;
+    }
+
+    let status = match f() {
+        Ok(()) => Status::Active,
+        Err(err) => {
+            let msg = format!("{:#}", err);
+            output_debug_string(format!("{} init failed: {}", name, msg).as_str());
+            if let Ok(mut w) = log::LOG.lock() {
+                w.write_all(format!("error {}::init: {}\n", name, msg).as_bytes())
+                    .ok();
+            }
+            Status::Failed(msg)
+        }
+    };
+    let active = matches!(status, Status::Active);
+    set(name, status);
+    active
+}
+
+/// One line per subsystem, in init order.
+pub(crate) fn report() -> Vec<String> {
+    STATUS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, status)| match status {
+            Status::Active => format!("{}: active", name),
+            Status::Failed(err) => format!("{}: failed ({})", name, err),
+            Status::Skipped(missing) => format!("{}: disabled (requires {})", name, missing),
+        })
+        .collect()
+}
@@ -2,36 +2,194 @@ extern crate proc_macro;
 
 use once_cell::sync::OnceCell;
 use proc_macro::TokenStream;
-use syn::{parse_macro_input, LitInt};
-use versionlib::VersionlibData;
-
-fn get_versionlib_data() -> &'static VersionlibData {
-    static VERSIONLIB_DATA: OnceCell<VersionlibData> = OnceCell::new();
-    VERSIONLIB_DATA.get_or_init(|| {
-        let target_version = std::fs::read("target_version.txt").unwrap();
-        let target_version = String::from_utf8(target_version).unwrap();
-        versionlib::load(&format!(
-            "versionlib/bin/versionlib-{}.bin",
-            target_version.replace(".", "-").trim()
-        ))
-        .unwrap()
+use proc_macro2::Span;
+use syn::{Ident, LitInt};
+use versionlib::{AddressNames, CompactVersionlibData};
+
+const TARGET_VERSION_PATH: &str = "target_version.txt";
+const BIN_DIR: &str = "versionlib/bin";
+const NAMES_PATH: &str = "addresses.toml";
+// Oldest runtime the plugin loads on, see MIN_RUNTIME_VERSION in src/address.rs
+#[cfg(feature = "check-all-versions")]
+const MIN_VERSION: [u32; 4] = [1, 6, 1130, 0];
+
+struct Target {
+    version: [u32; 4],
+    path: String,
+    data: CompactVersionlibData,
+}
+
+fn version_string(version: [u32; 4]) -> String {
+    version.map(|v| v.to_string()).join(".")
+}
+
+fn load_target() -> Result<Target, String> {
+    let target_version = std::fs::read_to_string(TARGET_VERSION_PATH)
+        .map_err(|e| format!("could not read {}: {}", TARGET_VERSION_PATH, e))?;
+    let version: [u32; 4] = target_version
+        .trim()
+        .split('.')
+        .map(|v| v.parse().ok())
+        .collect::<Option<Vec<_>>>()
+        .and_then(|v| v.try_into().ok())
+        .ok_or_else(|| {
+            format!(
+                "{} should contain a version like 1.6.1170.0, found {:?}",
+                TARGET_VERSION_PATH,
+                target_version.trim()
+            )
+        })?;
+    let path = format!("{}/{}", BIN_DIR, versionlib::file_name(version));
+    let data = versionlib::open_compact(&path).map_err(|e| {
+        format!(
+            "could not load {} for target version {}: {}",
+            path,
+            version_string(version),
+            e
+        )
+    })?;
+    Ok(Target {
+        version,
+        path,
+        data,
+    })
+}
+
+fn get_versionlib_data() -> &'static Result<Target, String> {
+    static VERSIONLIB_DATA: OnceCell<Result<Target, String>> = OnceCell::new();
+    VERSIONLIB_DATA.get_or_init(load_target)
+}
+
+fn get_address_names() -> &'static Result<AddressNames, String> {
+    static ADDRESS_NAMES: OnceCell<Result<AddressNames, String>> = OnceCell::new();
+    ADDRESS_NAMES.get_or_init(|| {
+        AddressNames::load(NAMES_PATH).map_err(|e| format!("could not load {}: {}", NAMES_PATH, e))
     })
 }
 
+// Every database in versionlib/bin for a supported version, sorted by version.
+#[cfg(feature = "check-all-versions")]
+fn get_all_versionlib_data() -> &'static Result<Vec<(String, CompactVersionlibData)>, String> {
+    static ALL_VERSIONLIB_DATA: OnceCell<Result<Vec<(String, CompactVersionlibData)>, String>> =
+        OnceCell::new();
+    ALL_VERSIONLIB_DATA.get_or_init(|| {
+        let mut all = Vec::new();
+        let entries =
+            std::fs::read_dir(BIN_DIR).map_err(|e| format!("could not read {}: {}", BIN_DIR, e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            let path = path.to_string_lossy().to_string();
+            let data = versionlib::open_compact(&path)
+                .map_err(|e| format!("could not load {}: {}", path, e))?;
+            if data.version >= MIN_VERSION {
+                all.push((path, data));
+            }
+        }
+        all.sort_by_key(|(_, data)| data.version);
+        Ok(all)
+    })
+}
+
+fn compile_error(span: Span, msg: String) -> TokenStream {
+    syn::Error::new(span, msg).to_compile_error().into()
+}
+
+// Returns the error message listing the databases that lack `address_id`, if any.
+#[cfg(feature = "check-all-versions")]
+fn check_all_versions(address_id: u64) -> Option<String> {
+    let all = match get_all_versionlib_data() {
+        Ok(all) => all,
+        Err(err) => return Some(err.clone()),
+    };
+    let missing: Vec<String> = all
+        .iter()
+        .filter(|(_, data)| data.get(address_id).is_none())
+        .map(|(path, data)| format!("{} ({})", version_string(data.version), path))
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "address id {} is missing in {} of {} versions: {}",
+        address_id,
+        missing.len(),
+        all.len(),
+        missing.join(", ")
+    ))
+}
+
+#[cfg(not(feature = "check-all-versions"))]
+fn check_all_versions(_address_id: u64) -> Option<String> {
+    None
+}
+
+// `versionlib!(51109)` or `versionlib!(ConsoleLog_Print)`, looked up in addresses.toml
+fn parse_address_id(input: TokenStream) -> Result<(u64, Span), syn::Error> {
+    if let Ok(lit) = syn::parse::<LitInt>(input.clone()) {
+        return Ok((lit.base10_parse()?, lit.span()));
+    }
+    let ident = syn::parse::<Ident>(input).map_err(|e| {
+        syn::Error::new(
+            e.span(),
+            "expected an address id or a name from addresses.toml",
+        )
+    })?;
+    let names = get_address_names()
+        .as_ref()
+        .map_err(|err| syn::Error::new(ident.span(), err))?;
+    let name = ident.to_string();
+    match names.id(&name) {
+        Some(address_id) => Ok((address_id, ident.span())),
+        None => Err(syn::Error::new(
+            ident.span(),
+            format!("{} is not defined in {}", name, NAMES_PATH),
+        )),
+    }
+}
+
 #[proc_macro]
 pub fn versionlib(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as LitInt);
+    let (address_id, span) = match parse_address_id(input) {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
-    let data = get_versionlib_data();
-    let address_id: u64 = input.base10_parse().unwrap();
-    let value = data.data[&address_id];
-    format!("{value:#x}").parse().unwrap()
+    let target = match get_versionlib_data() {
+        Ok(target) => target,
+        Err(err) => return compile_error(span, err.clone()),
+    };
+    // fail the build early if the id is unknown to the target version
+    if target.data.get(address_id).is_none() {
+        return compile_error(
+            span,
+            format!(
+                "address id {} is not in {} (target version {})",
+                address_id,
+                target.path,
+                version_string(target.version)
+            ),
+        );
+    }
+    if let Some(err) = check_all_versions(address_id) {
+        return compile_error(span, err);
+    }
+    // the actual offset is resolved at runtime for the running game version. Including the
+    // manifest makes cargo rebuild the caller when a name in it changes.
+    format!(
+        "{{ const _: &[u8] = include_bytes!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/{NAMES_PATH}\")); \
+         crate::address::resolve({address_id}) }}"
+    )
+    .parse()
+    .unwrap()
 }
 
 #[proc_macro]
 pub fn target_version(_input: TokenStream) -> TokenStream {
-    let data = get_versionlib_data();
-    let version = data.version;
+    let target = match get_versionlib_data() {
+        Ok(target) => target,
+        Err(err) => return compile_error(Span::call_site(), err.clone()),
+    };
+    let version = target.version;
     let packed = ((version[0] & 0xff) << 24)
         | ((version[1] & 0xff) << 16)
         | ((version[2] & 0xfff) << 4)
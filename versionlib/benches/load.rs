@@ -0,0 +1,90 @@
+// Load time and memory per bin: cargo bench --target x86_64-unknown-linux-gnu
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const ITERATIONS: u32 = 5;
+
+// Returns (average time, bytes retained by the result, peak bytes while loading)
+fn measure<T, F: Fn() -> T>(f: F) -> (Duration, usize, usize) {
+    let mut elapsed = Duration::ZERO;
+    let mut retained = 0;
+    let mut peak = 0;
+    for _ in 0..ITERATIONS {
+        let before = ALLOCATED.load(Ordering::Relaxed);
+        PEAK.store(before, Ordering::Relaxed);
+        let start = Instant::now();
+        let result = f();
+        elapsed += start.elapsed();
+        retained = ALLOCATED.load(Ordering::Relaxed) - before;
+        peak = PEAK.load(Ordering::Relaxed) - before;
+        drop(result);
+    }
+    (elapsed / ITERATIONS, retained, peak)
+}
+
+fn main() {
+    let mut paths: Vec<_> = std::fs::read_dir("bin")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+
+    println!(
+        "{:<32} {:<10} {:>10} {:>10} {:>10} {:>10}",
+        "bin", "method", "time(ms)", "kib", "peak(kib)", "count"
+    );
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let bytes = std::fs::read(&path).unwrap();
+        let count = versionlib::load_from_bytes(&bytes).unwrap().data.len();
+
+        let path = path.to_str().unwrap();
+        let rows = [
+            ("load", measure(|| versionlib::load(path).unwrap())),
+            (
+                "bytes",
+                measure(|| versionlib::load_from_bytes(&bytes).unwrap()),
+            ),
+            (
+                "compact",
+                measure(|| versionlib::load_compact_from_bytes(&bytes).unwrap()),
+            ),
+        ];
+        for (method, (time, retained, peak)) in rows {
+            println!(
+                "{:<32} {:<10} {:>10.2} {:>10} {:>10} {:>10}",
+                name,
+                method,
+                time.as_secs_f64() * 1000.0,
+                retained / 1024,
+                peak / 1024,
+                count
+            );
+        }
+    }
+}
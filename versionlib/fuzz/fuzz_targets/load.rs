@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = versionlib::load_from_bytes(data);
+    let _ = versionlib::load_compact_from_bytes(data);
+});
@@ -0,0 +1,117 @@
+// Parser for the VR address library (version-1-4-15-0.csv)
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use crate::{VersionlibData, VersionlibError};
+
+const VR_MODULE_NAME: &str = "SkyrimVR.exe";
+
+fn parse_version(s: &str) -> Option<[u32; 4]> {
+    let parts = s
+        .trim()
+        .split('.')
+        .map(|p| p.parse().ok())
+        .collect::<Option<Vec<u32>>>()?;
+    let mut version = [0; 4];
+    if parts.is_empty() || parts.len() > 4 {
+        return None;
+    }
+    version[..parts.len()].copy_from_slice(&parts);
+    Some(version)
+}
+
+fn parse_offset(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u64::from_str_radix(hex, 16).ok()
+}
+
+/// Loads a CSV address library.
+///
+/// The first line is an `id,offset` header, followed by a `<count>,<version>` row
+/// and one `<id>,<hex offset>` row per address.
+pub fn load_csv(filename: &str) -> Result<VersionlibData, VersionlibError> {
+    let f = File::open(filename).map_err(|e| VersionlibError::Open {
+        reason: e.to_string(),
+    })?;
+
+    let mut version = [0; 4];
+    let mut data = hashbrown::HashMap::<u64, u64>::new();
+    let mut rdata = hashbrown::HashMap::<u64, u64>::new();
+
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line.map_err(|e| VersionlibError::Read {
+            reason: e.to_string(),
+        })?;
+        let csv_err = |reason: &str| VersionlibError::Csv {
+            line: i + 1,
+            reason: reason.to_string(),
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split(',');
+        let first = fields.next().unwrap_or("").trim();
+        let second = fields.next().ok_or_else(|| csv_err("expected 2 columns"))?;
+
+        // header
+        if first.parse::<u64>().is_err() && data.is_empty() && i == 0 {
+            continue;
+        }
+        // <count>,<version>
+        if second.contains('.') && data.is_empty() {
+            version = parse_version(second).ok_or_else(|| csv_err("invalid version"))?;
+            let count = first.parse().map_err(|_| csv_err("invalid count"))?;
+            data.reserve(count);
+            rdata.reserve(count);
+            continue;
+        }
+
+        let id = first.parse().map_err(|_| csv_err("invalid id"))?;
+        let offset = parse_offset(second).ok_or_else(|| csv_err("invalid offset"))?;
+        if data.insert(id, offset).is_some() {
+            return Err(csv_err("duplicate id"));
+        }
+        rdata.insert(offset, id);
+    }
+
+    Ok(VersionlibData {
+        version,
+        module_name: Ok(String::from(VR_MODULE_NAME)),
+        module_name_raw: VR_MODULE_NAME.as_bytes().to_vec(),
+        ptr_size: 8,
+        data,
+        rdata,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let path = std::env::temp_dir().join(format!("version-vr-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "id,offset\n3,1.4.15.0\n10,0x1000\n11,1040\n401203,2F9A800\n",
+        )
+        .unwrap();
+        let result = load_csv(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!([1, 4, 15, 0], result.version);
+        assert_eq!(Ok(VR_MODULE_NAME), result.module_name.as_deref());
+        assert_eq!(3, result.data.len());
+        assert_eq!(0x1000, result.data[&10]);
+        assert_eq!(0x1040, result.data[&11]);
+        assert_eq!(401203, result.rdata[&0x2f9a800]);
+    }
+}
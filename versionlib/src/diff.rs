@@ -0,0 +1,73 @@
+use crate::VersionlibData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added { offset: u64 },
+    Removed { offset: u64 },
+    Moved { old: u64, new: u64 },
+    Unchanged { offset: u64 },
+}
+
+/// Compares a single address id between two databases.
+/// Returns `None` if neither database has it.
+pub fn compare(old: &VersionlibData, new: &VersionlibData, id: u64) -> Option<Change> {
+    match (old.data.get(&id), new.data.get(&id)) {
+        (None, None) => None,
+        (None, Some(&offset)) => Some(Change::Added { offset }),
+        (Some(&offset), None) => Some(Change::Removed { offset }),
+        (Some(&old), Some(&new)) if old == new => Some(Change::Unchanged { offset: old }),
+        (Some(&old), Some(&new)) => Some(Change::Moved { old, new }),
+    }
+}
+
+/// Compares every address id of two databases, sorted by id.
+pub fn diff(old: &VersionlibData, new: &VersionlibData) -> Vec<(u64, Change)> {
+    let mut ids: Vec<u64> = old
+        .data
+        .keys()
+        .chain(new.data.keys().filter(|id| !old.data.contains_key(*id)))
+        .copied()
+        .collect();
+    ids.sort_unstable();
+    ids.into_iter()
+        .filter_map(|id| compare(old, new, id).map(|change| (id, change)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(entries: &[(u64, u64)]) -> VersionlibData {
+        VersionlibData {
+            version: [1, 6, 0, 0],
+            module_name: Ok(String::from("SkyrimSE.exe")),
+            module_name_raw: b"SkyrimSE.exe".to_vec(),
+            ptr_size: 8,
+            data: entries.iter().copied().collect(),
+            rdata: entries.iter().map(|&(k, v)| (v, k)).collect(),
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        let old = data(&[(1, 0x1000), (2, 0x2000), (3, 0x3000)]);
+        let new = data(&[(2, 0x2000), (3, 0x3010), (4, 0x4000)]);
+        assert_eq!(
+            vec![
+                (1, Change::Removed { offset: 0x1000 }),
+                (2, Change::Unchanged { offset: 0x2000 }),
+                (
+                    3,
+                    Change::Moved {
+                        old: 0x3000,
+                        new: 0x3010
+                    }
+                ),
+                (4, Change::Added { offset: 0x4000 }),
+            ],
+            diff(&old, &new)
+        );
+        assert_eq!(None, compare(&old, &new, 5));
+    }
+}
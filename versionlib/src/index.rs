@@ -0,0 +1,44 @@
+use crate::VersionlibData;
+
+/// Address ids sorted by offset, for mapping arbitrary addresses (e.g. from a crash log)
+/// back to the closest preceding id.
+pub struct OffsetIndex(Vec<(u64, u64)>);
+
+impl OffsetIndex {
+    pub fn new(data: &VersionlibData) -> Self {
+        let mut entries: Vec<(u64, u64)> = data.rdata.iter().map(|(&k, &v)| (k, v)).collect();
+        entries.sort_unstable();
+        OffsetIndex(entries)
+    }
+
+    /// Returns the id with the greatest offset not above `offset`, and the distance from it.
+    pub fn nearest_id(&self, offset: u64) -> Option<(u64, u64)> {
+        let pos = self.0.partition_point(|&(o, _)| o <= offset);
+        let (o, id) = self.0.get(pos.checked_sub(1)?)?;
+        Some((*id, offset - o))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let entries = [(10, 0x1000), (11, 0x1040), (12, 0x2000)];
+        let data = VersionlibData {
+            version: [1, 6, 0, 0],
+            module_name: Ok(String::from("SkyrimSE.exe")),
+            module_name_raw: b"SkyrimSE.exe".to_vec(),
+            ptr_size: 8,
+            data: entries.iter().copied().collect(),
+            rdata: entries.iter().map(|&(k, v)| (v, k)).collect(),
+        };
+        let index = OffsetIndex::new(&data);
+        assert_eq!(None, index.nearest_id(0xfff));
+        assert_eq!(Some((10, 0)), index.nearest_id(0x1000));
+        assert_eq!(Some((10, 0x3f)), index.nearest_id(0x103f));
+        assert_eq!(Some((11, 0)), index.nearest_id(0x1040));
+        assert_eq!(Some((12, 0x40)), index.nearest_id(0x2040));
+    }
+}
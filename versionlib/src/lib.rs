@@ -1,18 +1,43 @@
 // Ported versionlibdb.h from https://www.nexusmods.com/skyrimspecialedition/mods/32444
-use std::{fs::File, io::Read};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
+mod csv;
+mod diff;
+mod index;
+mod names;
+mod writer;
+
+pub use csv::load_csv;
+pub use diff::{compare, diff, Change};
+pub use index::OffsetIndex;
+pub use names::AddressNames;
+pub use writer::{save, write};
+
 #[derive(thiserror::Error, Debug)]
 pub enum VersionlibError {
     #[error("could not open the file: {reason}")]
     Open { reason: String },
     #[error("could not read the file: {reason}")]
     Read { reason: String },
+    #[error("could not create the file: {reason}")]
+    Create { reason: String },
+    #[error("could not write the file: {reason}")]
+    Write { reason: String },
     #[error("unexpected format: {format}")]
     Format { format: u32 },
     #[error("unexpected tn_len: {tn_len}")]
     TnLenRange { tn_len: i32 },
+    #[error("invalid csv at line {line}: {reason}")]
+    Csv { line: usize, reason: String },
+    #[error("invalid address names: {reason}")]
+    Names { reason: String },
+    #[error("corrupted database at byte {position}: {reason}")]
+    Corrupt { position: u64, reason: String },
 }
 
 pub struct VersionlibData {
@@ -24,226 +49,611 @@ pub struct VersionlibData {
     pub rdata: hashbrown::HashMap<u64, u64>,
 }
 
+/// Conventional address library file name for a game version:
+/// `version-*.bin` for SE 1.5.x, `version-*.csv` for VR 1.4.x and `versionlib-*.bin` for AE 1.6.x.
+pub fn file_name(version: [u32; 4]) -> String {
+    let [major, minor, build, sub] = version;
+    match (major, minor) {
+        (1, 4) => format!("version-{}-{}-{}-{}.csv", major, minor, build, sub),
+        (1, 5) => format!("version-{}-{}-{}-{}.bin", major, minor, build, sub),
+        _ => format!("versionlib-{}-{}-{}-{}.bin", major, minor, build, sub),
+    }
+}
+
+/// Loads `filename` with [`load_csv`] if it has a .csv extension, otherwise with [`load`].
+pub fn open(filename: &str) -> Result<VersionlibData, VersionlibError> {
+    if filename.to_ascii_lowercase().ends_with(".csv") {
+        load_csv(filename)
+    } else {
+        load(filename)
+    }
+}
+
+/// Loads a format 1 (SE 1.5.x `version-*.bin`) or format 2 (AE `versionlib-*.bin`) database.
+/// Both formats share the same layout.
 pub fn load(filename: &str) -> Result<VersionlibData, VersionlibError> {
-    let mut f = File::open(filename).map_err(|e| VersionlibError::Open {
+    let f = File::open(filename).map_err(|e| VersionlibError::Open {
         reason: e.to_string(),
     })?;
+    load_from_reader(BufReader::new(f))
+}
+
+/// Same as [`load`], for a database that is already in memory (e.g. `include_bytes!`).
+pub fn load_from_bytes(bytes: &[u8]) -> Result<VersionlibData, VersionlibError> {
+    load_from_reader(bytes)
+}
 
-    let format = f
-        .read_u32::<LittleEndian>()
-        .map_err(|e| VersionlibError::Read {
-            reason: e.to_string(),
-        })?;
+/// Same as [`load`], reading from `r`. `r` should be buffered.
+pub fn load_from_reader<R: Read>(r: R) -> Result<VersionlibData, VersionlibError> {
+    let mut decoder = Decoder::new(r)?;
+    let header = decoder.header().clone();
 
-    if format != 2 {
-        return Err(VersionlibError::Format { format });
+    let capacity = header.addr_count.min(MAX_PREALLOCATED_ENTRIES) as usize;
+    let mut data = hashbrown::HashMap::<u64, u64>::with_capacity(capacity);
+    let mut rdata = hashbrown::HashMap::<u64, u64>::with_capacity(capacity);
+
+    loop {
+        let position = decoder.position();
+        let (id, offset) = match decoder.next() {
+            Some(entry) => entry?,
+            None => break,
+        };
+        if data.insert(id, offset).is_some() {
+            return Err(VersionlibError::Corrupt {
+                position,
+                reason: format!("duplicate id {}", id),
+            });
+        }
+        rdata.insert(offset, id);
     }
 
-    let version: [u32; 4] = (0..4)
-        .map(|_| {
-            f.read_u32::<LittleEndian>()
-                .map_err(|e| VersionlibError::Read {
-                    reason: e.to_string(),
-                })
-        })
-        .collect::<Result<Vec<_>, _>>()?
-        .try_into()
-        .unwrap();
-
-    let tn_len = f
-        .read_i32::<LittleEndian>()
-        .map_err(|e| VersionlibError::Read {
-            reason: e.to_string(),
-        })?;
-
-    if tn_len < 0 || tn_len >= 0x10000 {
-        return Err(VersionlibError::TnLenRange { tn_len });
+    Ok(VersionlibData {
+        version: header.version,
+        module_name: String::from_utf8(header.module_name_raw.clone()),
+        module_name_raw: header.module_name_raw,
+        ptr_size: header.ptr_size,
+        data,
+        rdata,
+    })
+}
+
+// addr_count comes from the file; don't trust it for more than a few times the real counts
+const MAX_PREALLOCATED_ENTRIES: u32 = 0x100000;
+
+#[derive(Debug, Clone)]
+pub struct VersionlibHeader {
+    pub format: u32,
+    pub version: [u32; 4],
+    pub module_name_raw: Vec<u8>,
+    pub ptr_size: u32,
+    pub addr_count: u32,
+}
+
+// Keeps track of the byte position for error reporting
+struct CountingReader<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
     }
+}
+
+/// Streams the `(id, offset)` entries of a bin without building any map.
+///
+/// Any inconsistency is reported as [`VersionlibError::Corrupt`], after which the iterator ends.
+pub struct Decoder<R> {
+    r: CountingReader<R>,
+    header: VersionlibHeader,
+    remaining: u32,
+    pvid: u64,
+    poffset: u64,
+}
+
+impl<R: Read> Decoder<R> {
+    /// Reads the header of the database.
+    pub fn new(r: R) -> Result<Self, VersionlibError> {
+        let mut r = CountingReader {
+            inner: r,
+            position: 0,
+        };
+
+        let format = r.read_u32::<LittleEndian>().map_err(|e| read_err(e, 0))?;
+
+        if format != 1 && format != 2 {
+            return Err(VersionlibError::Format { format });
+        }
+
+        let mut version = [0; 4];
+        for v in version.iter_mut() {
+            *v = r
+                .read_u32::<LittleEndian>()
+                .map_err(|e| read_err(e, r.position))?;
+        }
+
+        let tn_len = r
+            .read_i32::<LittleEndian>()
+            .map_err(|e| read_err(e, r.position))?;
+
+        if !(0..0x10000).contains(&tn_len) {
+            return Err(VersionlibError::TnLenRange { tn_len });
+        }
+
+        let mut module_name_raw = vec![0; tn_len as usize];
+        r.read_exact(&mut module_name_raw)
+            .map_err(|e| read_err(e, r.position))?;
 
-    let mut module_name_raw = vec![0; tn_len as usize];
-    f.read_exact(&mut module_name_raw)
-        .map_err(|e| VersionlibError::Read {
-            reason: e.to_string(),
-        })?;
-    let module_name = String::from_utf8(module_name_raw.clone());
-
-    let ptr_size: u32 = f
-        .read_u32::<LittleEndian>()
-        .map_err(|e| VersionlibError::Read {
-            reason: e.to_string(),
-        })?;
-
-    let addr_count: u32 = f
-        .read_u32::<LittleEndian>()
-        .map_err(|e| VersionlibError::Read {
-            reason: e.to_string(),
-        })?;
-
-    let mut b1: u8;
-    let mut b2: u8;
-    let mut w1: u16;
-    let mut w2: u16;
-    let mut d1: u32;
-    let mut d2: u32;
-    let mut q1: u64;
-    let mut q2: u64;
-
-    let mut pvid: u64 = 0;
-    let mut poffset: u64 = 0;
-    let mut tpoffset: u64;
-
-    let mut data = hashbrown::HashMap::<u64, u64>::with_capacity(addr_count as _);
-    let mut rdata = hashbrown::HashMap::<u64, u64>::with_capacity(addr_count as _);
-
-    for _ in 0..addr_count {
-        let type_ = f.read_u8().map_err(|e| VersionlibError::Read {
-            reason: e.to_string(),
-        })?;
+        let ptr_size = r
+            .read_u32::<LittleEndian>()
+            .map_err(|e| read_err(e, r.position))?;
+        let addr_count = r
+            .read_u32::<LittleEndian>()
+            .map_err(|e| read_err(e, r.position))?;
+
+        let mut decoder = Decoder {
+            r,
+            header: VersionlibHeader {
+                format,
+                version,
+                module_name_raw,
+                ptr_size,
+                addr_count,
+            },
+            remaining: addr_count,
+            pvid: 0,
+            poffset: 0,
+        };
+        if addr_count == 0 {
+            decoder.check_trailing_data()?;
+        }
+        Ok(decoder)
+    }
+
+    pub fn header(&self) -> &VersionlibHeader {
+        &self.header
+    }
+
+    /// Byte position of the next entry.
+    pub fn position(&self) -> u64 {
+        self.r.position
+    }
+
+    fn read_entry(&mut self) -> Result<(u64, u64), VersionlibError> {
+        let start = self.r.position;
+        let corrupt = |reason: &str| VersionlibError::Corrupt {
+            position: start,
+            reason: reason.to_string(),
+        };
+        let r = &mut self.r;
+        let ptr_size = self.header.ptr_size as u64;
+        let pvid = self.pvid;
+
+        let type_ = r.read_u8().map_err(|e| read_err(e, start))?;
         let type_low = type_ & 0xF;
         let type_high = type_ >> 4;
 
-        match type_low {
-            0 => {
-                q1 = f
-                    .read_u64::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?
-            }
-            1 => q1 = pvid + 1,
-            2 => {
-                b1 = f.read_u8().map_err(|e| VersionlibError::Read {
-                    reason: e.to_string(),
-                })?;
-                q1 = pvid + b1 as u64;
-            }
-            3 => {
-                b1 = f.read_u8().map_err(|e| VersionlibError::Read {
-                    reason: e.to_string(),
-                })?;
-                q1 = pvid - b1 as u64;
-            }
-            4 => {
-                w1 = f
-                    .read_u16::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?;
-                q1 = pvid + w1 as u64;
-            }
-            5 => {
-                w1 = f
-                    .read_u16::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?;
-                q1 = pvid - w1 as u64;
-            }
-            6 => {
-                w1 = f
-                    .read_u16::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?;
-                q1 = w1 as _;
-            }
-            7 => {
-                d1 = f
-                    .read_u32::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?;
-                q1 = d1 as _;
-            }
-            _ => unreachable!(),
+        let q1 = match type_low {
+            0 => Some(
+                r.read_u64::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))?,
+            ),
+            1 => pvid.checked_add(1),
+            2 => pvid.checked_add(r.read_u8().map_err(|e| read_err(e, start))? as u64),
+            3 => pvid.checked_sub(r.read_u8().map_err(|e| read_err(e, start))? as u64),
+            4 => pvid.checked_add(
+                r.read_u16::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))? as u64,
+            ),
+            5 => pvid.checked_sub(
+                r.read_u16::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))? as u64,
+            ),
+            6 => Some(
+                r.read_u16::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))? as u64,
+            ),
+            7 => Some(
+                r.read_u32::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))? as u64,
+            ),
+            _ => return Err(corrupt(&format!("invalid id type {}", type_low))),
         }
+        .ok_or_else(|| corrupt("id out of range"))?;
 
-        tpoffset = if (type_high & 8) != 0 {
-            poffset / ptr_size as u64
+        let scaled = (type_high & 8) != 0;
+        if scaled && ptr_size == 0 {
+            return Err(corrupt("scaled offset with ptr_size 0"));
+        }
+        let tpoffset = if scaled {
+            self.poffset / ptr_size
         } else {
-            poffset
+            self.poffset
         };
 
-        match type_high & 7 {
-            0 => {
-                q2 = f
-                    .read_u64::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?
-            }
-            1 => q2 = tpoffset + 1,
-            2 => {
-                b2 = f.read_u8().map_err(|e| VersionlibError::Read {
-                    reason: e.to_string(),
-                })?;
-                q2 = tpoffset + b2 as u64;
-            }
-            3 => {
-                b2 = f.read_u8().map_err(|e| VersionlibError::Read {
-                    reason: e.to_string(),
-                })?;
-                q2 = tpoffset - b2 as u64;
-            }
-            4 => {
-                w2 = f
-                    .read_u16::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?;
-                q2 = tpoffset + w2 as u64;
-            }
-            5 => {
-                w2 = f
-                    .read_u16::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?;
-                q2 = tpoffset - w2 as u64;
-            }
-            6 => {
-                w2 = f
-                    .read_u16::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?;
-                q2 = w2 as _;
-            }
-            7 => {
-                d2 = f
-                    .read_u32::<LittleEndian>()
-                    .map_err(|e| VersionlibError::Read {
-                        reason: e.to_string(),
-                    })?;
-                q2 = d2 as _;
-            }
+        let q2 = match type_high & 7 {
+            0 => Some(
+                r.read_u64::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))?,
+            ),
+            1 => tpoffset.checked_add(1),
+            2 => tpoffset.checked_add(r.read_u8().map_err(|e| read_err(e, start))? as u64),
+            3 => tpoffset.checked_sub(r.read_u8().map_err(|e| read_err(e, start))? as u64),
+            4 => tpoffset.checked_add(
+                r.read_u16::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))? as u64,
+            ),
+            5 => tpoffset.checked_sub(
+                r.read_u16::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))? as u64,
+            ),
+            6 => Some(
+                r.read_u16::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))? as u64,
+            ),
+            7 => Some(
+                r.read_u32::<LittleEndian>()
+                    .map_err(|e| read_err(e, start))? as u64,
+            ),
             _ => unreachable!(),
+        };
+
+        let q2 = if scaled {
+            q2.and_then(|q2| q2.checked_mul(ptr_size))
+        } else {
+            q2
         }
+        .ok_or_else(|| corrupt("offset out of range"))?;
+
+        self.poffset = q2;
+        self.pvid = q1;
 
-        if (type_high & 8) != 0 {
-            q2 *= ptr_size as u64;
+        Ok((q1, q2))
+    }
+
+    fn check_trailing_data(&mut self) -> Result<(), VersionlibError> {
+        let position = self.r.position;
+        let mut buf = [0; 1];
+        match self.r.read(&mut buf) {
+            Ok(0) => Ok(()),
+            Ok(_) => Err(VersionlibError::Corrupt {
+                position,
+                reason: String::from("trailing data"),
+            }),
+            Err(e) => Err(read_err(e, position)),
         }
+    }
+}
+
+fn read_err(e: std::io::Error, position: u64) -> VersionlibError {
+    if e.kind() == std::io::ErrorKind::UnexpectedEof {
+        return VersionlibError::Corrupt {
+            position,
+            reason: String::from("truncated"),
+        };
+    }
+    VersionlibError::Read {
+        reason: e.to_string(),
+    }
+}
 
-        data.insert(q1, q2);
-        rdata.insert(q2, q1);
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = Result<(u64, u64), VersionlibError>;
 
-        poffset = q2;
-        pvid = q1;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let mut entry = self.read_entry();
+        if entry.is_ok() && self.remaining == 0 {
+            if let Err(err) = self.check_trailing_data() {
+                entry = Err(err);
+            }
+        }
+        if entry.is_err() {
+            self.remaining = 0;
+        }
+        Some(entry)
     }
 
-    Ok(VersionlibData {
-        version,
-        module_name,
-        module_name_raw,
-        ptr_size,
-        data,
-        rdata,
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+/// A lighter alternative to [`VersionlibData`]: entries are kept in a single
+/// `Vec` sorted by id, and looked up with a binary search.
+pub struct CompactVersionlibData {
+    pub version: [u32; 4],
+    pub module_name_raw: Vec<u8>,
+    pub ptr_size: u32,
+    pub data: Vec<(u64, u64)>,
+}
+
+impl CompactVersionlibData {
+    pub fn get(&self, id: u64) -> Option<u64> {
+        let pos = self.data.binary_search_by_key(&id, |&(id, _)| id).ok()?;
+        Some(self.data[pos].1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl From<VersionlibData> for CompactVersionlibData {
+    fn from(data: VersionlibData) -> Self {
+        let mut entries: Vec<(u64, u64)> = data.data.into_iter().collect();
+        entries.sort_unstable();
+        CompactVersionlibData {
+            version: data.version,
+            module_name_raw: data.module_name_raw,
+            ptr_size: data.ptr_size,
+            data: entries,
+        }
+    }
+}
+
+/// Same as [`load_from_reader`], into a [`CompactVersionlibData`].
+pub fn load_compact_from_reader<R: Read>(r: R) -> Result<CompactVersionlibData, VersionlibError> {
+    let mut decoder = Decoder::new(r)?;
+    let header = decoder.header().clone();
+
+    let mut entries: Vec<(u64, u64)> =
+        Vec::with_capacity(header.addr_count.min(MAX_PREALLOCATED_ENTRIES) as usize);
+    // bins are normally written in ascending id order, which rules out duplicates
+    // without a set; one is only built once an id is out of order.
+    let mut seen: Option<hashbrown::HashSet<u64>> = None;
+    loop {
+        let position = decoder.position();
+        let (id, offset) = match decoder.next() {
+            Some(entry) => entry?,
+            None => break,
+        };
+        let duplicate = match (&mut seen, entries.last()) {
+            (Some(seen), _) => !seen.insert(id),
+            (None, Some(&(last, _))) if id <= last => {
+                let mut set: hashbrown::HashSet<u64> = entries.iter().map(|&(id, _)| id).collect();
+                let duplicate = !set.insert(id);
+                seen = Some(set);
+                duplicate
+            }
+            (None, _) => false,
+        };
+        if duplicate {
+            return Err(VersionlibError::Corrupt {
+                position,
+                reason: format!("duplicate id {}", id),
+            });
+        }
+        entries.push((id, offset));
+    }
+    if seen.is_some() {
+        entries.sort_unstable();
+    }
+
+    Ok(CompactVersionlibData {
+        version: header.version,
+        module_name_raw: header.module_name_raw,
+        ptr_size: header.ptr_size,
+        data: entries,
     })
 }
 
+/// Same as [`load_from_bytes`], into a [`CompactVersionlibData`].
+pub fn load_compact_from_bytes(bytes: &[u8]) -> Result<CompactVersionlibData, VersionlibError> {
+    load_compact_from_reader(bytes)
+}
+
+/// Same as [`open`], into a [`CompactVersionlibData`].
+pub fn open_compact(filename: &str) -> Result<CompactVersionlibData, VersionlibError> {
+    if filename.to_ascii_lowercase().ends_with(".csv") {
+        return load_csv(filename).map(CompactVersionlibData::from);
+    }
+    let f = File::open(filename).map_err(|e| VersionlibError::Open {
+        reason: e.to_string(),
+    })?;
+    load_compact_from_reader(BufReader::new(f))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    fn header(ptr_size: u32, addr_count: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for v in [2, 1, 6, 0, 0, 12] {
+            buf.extend_from_slice(&u32::to_le_bytes(v));
+        }
+        buf.extend_from_slice(b"SkyrimSE.exe");
+        buf.extend_from_slice(&u32::to_le_bytes(ptr_size));
+        buf.extend_from_slice(&u32::to_le_bytes(addr_count));
+        buf
+    }
+
+    fn encode(entries: &[(u64, u64)]) -> Vec<u8> {
+        let data = VersionlibData {
+            version: [1, 6, 0, 0],
+            module_name: Ok(String::from("SkyrimSE.exe")),
+            module_name_raw: b"SkyrimSE.exe".to_vec(),
+            ptr_size: 8,
+            data: entries.iter().copied().collect(),
+            rdata: entries.iter().map(|&(k, v)| (v, k)).collect(),
+        };
+        let mut buf = Vec::new();
+        write(&data, &mut buf).unwrap();
+        buf
+    }
+
+    fn corrupt_at(result: Result<VersionlibData, VersionlibError>) -> Option<(u64, String)> {
+        match result {
+            Err(VersionlibError::Corrupt { position, reason }) => Some((position, reason)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn corrupted() {
+        let body_start = header(8, 0).len() as u64;
+
+        // id 5 at offset 1, then id 5 - 6
+        let mut buf = header(8, 2);
+        buf.extend_from_slice(&[0x12, 5, 0x13, 6]);
+        assert_eq!(
+            Some((body_start + 2, String::from("id out of range"))),
+            corrupt_at(load_from_bytes(&buf))
+        );
+
+        let mut buf = header(8, 2);
+        buf.extend_from_slice(&[0x12, 5, 0x12, 0]);
+        assert_eq!(
+            Some((body_start + 2, String::from("duplicate id 5"))),
+            corrupt_at(load_from_bytes(&buf))
+        );
+        assert!(matches!(
+            load_compact_from_bytes(&buf),
+            Err(VersionlibError::Corrupt { position, .. }) if position == body_start + 2
+        ));
+
+        let mut buf = header(8, 1);
+        buf.extend_from_slice(&[0x18]);
+        assert_eq!(
+            Some((body_start, String::from("invalid id type 8"))),
+            corrupt_at(load_from_bytes(&buf))
+        );
+
+        let mut buf = header(0, 1);
+        buf.extend_from_slice(&[0x91]);
+        assert_eq!(
+            Some((body_start, String::from("scaled offset with ptr_size 0"))),
+            corrupt_at(load_from_bytes(&buf))
+        );
+
+        let mut buf = header(8, 1);
+        buf.extend_from_slice(&[0x12, 5, 0xff]);
+        assert_eq!(
+            Some((body_start + 2, String::from("trailing data"))),
+            corrupt_at(load_from_bytes(&buf))
+        );
+
+        let mut buf = header(8, 2);
+        buf.extend_from_slice(&[0x12, 5, 0x00]);
+        assert_eq!(
+            Some((body_start + 2, String::from("truncated"))),
+            corrupt_at(load_from_bytes(&buf))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_bytes(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let _ = load_from_bytes(&bytes);
+            let _ = load_compact_from_bytes(&bytes);
+        }
+
+        #[test]
+        fn arbitrary_entries(
+            ptr_size in 0u32..16,
+            addr_count in any::<u32>(),
+            body in proptest::collection::vec(any::<u8>(), 0..512),
+        ) {
+            let mut buf = header(ptr_size, addr_count);
+            buf.extend_from_slice(&body);
+            let _ = load_from_bytes(&buf);
+            let _ = load_compact_from_bytes(&buf);
+        }
+
+        #[test]
+        fn round_trip(entries in proptest::collection::btree_map(any::<u64>(), any::<u64>(), 0..64)) {
+            let entries: Vec<(u64, u64)> = entries.into_iter().collect();
+            let buf = encode(&entries);
+            let data = load_from_bytes(&buf).unwrap();
+            prop_assert_eq!(entries.len(), data.data.len());
+            for (id, offset) in entries.iter() {
+                prop_assert_eq!(Some(offset), data.data.get(id));
+            }
+            let compact = load_compact_from_bytes(&buf).unwrap();
+            prop_assert_eq!(entries, compact.data);
+        }
+
+        #[test]
+        fn truncated(
+            entries in proptest::collection::btree_map(any::<u64>(), any::<u64>(), 1..64),
+            cut in any::<proptest::sample::Index>(),
+        ) {
+            let entries: Vec<(u64, u64)> = entries.into_iter().collect();
+            let buf = encode(&entries);
+            let cut = cut.index(buf.len());
+            let result = corrupt_at(load_from_bytes(&buf[..cut]));
+            prop_assert_eq!(Some(String::from("truncated")), result.map(|(_, reason)| reason));
+        }
+    }
+
+    #[test]
+    fn format_1() {
+        // a hand-built SE 1.5.97 database
+        let mut buf = Vec::new();
+        for v in [1, 1, 5, 97, 0, 12] {
+            buf.extend_from_slice(&u32::to_le_bytes(v));
+        }
+        buf.extend_from_slice(b"SkyrimSE.exe");
+        buf.extend_from_slice(&u32::to_le_bytes(8));
+        buf.extend_from_slice(&u32::to_le_bytes(4));
+        // id 10 (u64) at 0x1000 (u32)
+        buf.push(0x70);
+        buf.extend_from_slice(&u64::to_le_bytes(10));
+        buf.extend_from_slice(&u32::to_le_bytes(0x1000));
+        // id 10 + 2 at 0x1000 + 1
+        buf.extend_from_slice(&[0x12, 2]);
+        // id 12 + 0x100 (u16) at (0x1001 / 8 + 3) * 8
+        buf.push(0xa4);
+        buf.extend_from_slice(&u16::to_le_bytes(0x100));
+        buf.push(3);
+        // id 51109 (u32) at 0x2000 (u16)
+        buf.push(0x67);
+        buf.extend_from_slice(&u32::to_le_bytes(51109));
+        buf.extend_from_slice(&u16::to_le_bytes(0x2000));
+
+        assert_eq!(1, Decoder::new(&buf[..]).unwrap().header().format);
+        let path =
+            std::env::temp_dir().join(format!("version-format-1-{}.bin", std::process::id()));
+        std::fs::write(&path, &buf).unwrap();
+        let result = load(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        let data = result.unwrap();
+        assert_eq!([1, 5, 97, 0], data.version);
+        assert_eq!(Ok("SkyrimSE.exe"), data.module_name.as_deref());
+        let mut entries: Vec<(u64, u64)> = data.data.into_iter().collect();
+        entries.sort_unstable();
+        assert_eq!(
+            vec![(10, 0x1000), (12, 0x1001), (268, 0x1018), (51109, 0x2000)],
+            entries
+        );
+
+        buf[0] = 3;
+        let path =
+            std::env::temp_dir().join(format!("version-format-3-{}.bin", std::process::id()));
+        std::fs::write(&path, &buf).unwrap();
+        let result = load(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        assert!(matches!(result, Err(VersionlibError::Format { format: 3 })));
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!("version-1-5-97-0.bin", file_name([1, 5, 97, 0]));
+        assert_eq!("version-1-4-15-0.csv", file_name([1, 4, 15, 0]));
+        assert_eq!("versionlib-1-6-1170-0.bin", file_name([1, 6, 1170, 0]));
+    }
 
     #[test]
     fn it_works() {
@@ -268,4 +678,17 @@ mod tests {
         assert_eq!(0x1699720, result.data[&195890]);
         assert_eq!(0x398f70, result.data[&25259]);
     }
+
+    #[test]
+    fn compact() {
+        let bytes = std::fs::read("bin/versionlib-1-6-323-0.bin").unwrap();
+        let data = load_from_bytes(&bytes).unwrap();
+        let compact = load_compact_from_bytes(&bytes).unwrap();
+        assert_eq!(data.version, compact.version);
+        assert_eq!(data.data.len(), compact.len());
+        for (id, offset) in data.data.iter() {
+            assert_eq!(Some(*offset), compact.get(*id));
+        }
+        assert_eq!(None, compact.get(u64::MAX));
+    }
 }
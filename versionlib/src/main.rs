@@ -0,0 +1,534 @@
+use std::io::{BufRead, Write};
+
+use clap::{AppSettings, Arg, SubCommand};
+use versionlib::{AddressNames, Change, OffsetIndex, VersionlibData};
+
+fn get_clap<'a, 'b>() -> clap::App<'a, 'b> {
+    let bin = || {
+        Arg::with_name("bin")
+            .help("versionlib database (e.g. versionlib-1-6-1170-0.bin)")
+            .required(true)
+    };
+    clap::App::new("versionlib")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("inspect address library databases")
+        .setting(AppSettings::DisableHelpSubcommand)
+        .setting(AppSettings::VersionlessSubcommands)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("names")
+                .long("names")
+                .help("address names manifest (e.g. addresses.toml) for printing names")
+                .takes_value(true)
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("print the header (version, module name, pointer size, count)")
+                .arg(bin()),
+        )
+        .subcommand(
+            SubCommand::with_name("id")
+                .about("look up the offset of address ids")
+                .arg(bin())
+                .arg(
+                    Arg::with_name("id")
+                        .help("address id (decimal), or a name from --names")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("offset")
+                .about("look up the address id of offsets")
+                .arg(bin())
+                .arg(
+                    Arg::with_name("offset")
+                        .help("offset from the image base (hex with 0x prefix, or decimal)")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("dump the whole table sorted by id")
+                .arg(bin())
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .possible_values(&["csv", "json"])
+                        .default_value("csv"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("compare two databases, e.g. before and after a game update")
+                .arg(Arg::with_name("old").help("old database").required(true))
+                .arg(Arg::with_name("new").help("new database").required(true))
+                .arg(
+                    Arg::with_name("src")
+                        .long("src")
+                        .help("flag the ids of versionlib!() calls found in this source directory")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("id")
+                        .long("id")
+                        .help("flag this address id (or name from --names)")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .help("list every added, removed and moved id"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("symbolize")
+                .about("annotate <module>+0x... addresses in a crash log with the nearest id")
+                .arg(bin())
+                .arg(
+                    Arg::with_name("log")
+                        .help("crash log (reads stdin if omitted)")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("check that every name of --names exists in the databases")
+                .arg(
+                    Arg::with_name("bin")
+                        .help("versionlib databases")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+}
+
+const NAMES_FILE: &str = "addresses.toml";
+
+fn parse_u64(s: &str) -> Result<u64, String> {
+    let result = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    result.map_err(|e| format!("invalid number {:?}: {}", s, e))
+}
+
+fn load(matches: &clap::ArgMatches) -> Result<VersionlibData, String> {
+    let path = matches.value_of("bin").unwrap();
+    versionlib::open(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn load_names(matches: &clap::ArgMatches) -> Result<AddressNames, String> {
+    match matches.value_of("names") {
+        Some(path) => AddressNames::load(path).map_err(|e| format!("{}: {}", path, e)),
+        None => Ok(AddressNames::default()),
+    }
+}
+
+fn parse_id(names: &AddressNames, s: &str) -> Result<u64, String> {
+    match names.id(s) {
+        Some(id) => Ok(id),
+        None if s.starts_with(|c: char| c.is_ascii_digit()) => parse_u64(s),
+        None => Err(format!("unknown address name {:?}", s)),
+    }
+}
+
+// `51109 (ConsoleLog_Print)`, or just the id if it has no name
+fn label(names: &AddressNames, id: u64) -> String {
+    match names.name(id) {
+        Some(name) => format!("{} ({})", id, name),
+        None => id.to_string(),
+    }
+}
+
+fn version_string(version: [u32; 4]) -> String {
+    version.map(|v| v.to_string()).join(".")
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Collects the arguments of `versionlib!(<id or name>)` calls from the .rs files under `path`.
+fn scan_versionlib_calls(
+    path: &std::path::Path,
+    found: &mut Vec<(String, String)>,
+) -> std::io::Result<()> {
+    if path.is_dir() {
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            scan_versionlib_calls(&entry, found)?;
+        }
+        return Ok(());
+    }
+    if path.extension() != Some("rs".as_ref()) {
+        return Ok(());
+    }
+
+    const PATTERN: &str = "versionlib!(";
+    let source = std::fs::read_to_string(path)?;
+    for (line_no, line) in source.lines().enumerate() {
+        let mut rest = line;
+        while let Some(pos) = rest.find(PATTERN) {
+            rest = &rest[pos + PATTERN.len()..];
+            let arg = rest.split(')').next().unwrap_or("").trim();
+            if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                found.push((
+                    arg.to_string(),
+                    format!("{}:{}", path.display(), line_no + 1),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn sorted_entries(data: &VersionlibData) -> Vec<(u64, u64)> {
+    let mut entries: Vec<(u64, u64)> = data.data.iter().map(|(&k, &v)| (k, v)).collect();
+    entries.sort_unstable();
+    entries
+}
+
+fn info(matches: &clap::ArgMatches) -> Result<(), String> {
+    let data = load(matches)?;
+    println!("version: {}", version_string(data.version));
+    match &data.module_name {
+        Ok(name) => println!("module_name: {}", name),
+        Err(_) => println!("module_name: {:?} (not utf-8)", data.module_name_raw),
+    }
+    println!("ptr_size: {}", data.ptr_size);
+    println!("count: {}", data.data.len());
+    Ok(())
+}
+
+fn lookup_id(matches: &clap::ArgMatches) -> Result<bool, String> {
+    let data = load(matches)?;
+    let names = load_names(matches)?;
+    let mut found_all = true;
+    for id in matches.values_of("id").unwrap() {
+        let id = parse_id(&names, id)?;
+        match data.data.get(&id) {
+            Some(offset) => println!("{} {:#x}", label(&names, id), offset),
+            None => {
+                println!("{} not found", label(&names, id));
+                found_all = false;
+            }
+        }
+    }
+    Ok(found_all)
+}
+
+fn lookup_offset(matches: &clap::ArgMatches) -> Result<bool, String> {
+    let data = load(matches)?;
+    let names = load_names(matches)?;
+    let mut found_all = true;
+    for offset in matches.values_of("offset").unwrap() {
+        let offset = parse_u64(offset)?;
+        match data.rdata.get(&offset) {
+            Some(id) => println!("{:#x} {}", offset, label(&names, *id)),
+            None => {
+                println!("{:#x} not found", offset);
+                found_all = false;
+            }
+        }
+    }
+    Ok(found_all)
+}
+
+fn dump(matches: &clap::ArgMatches) -> Result<(), String> {
+    let data = load(matches)?;
+    let names = load_names(matches)?;
+    let entries = sorted_entries(&data);
+    let stdout = std::io::stdout();
+    let mut w = std::io::BufWriter::new(stdout.lock());
+
+    let result: std::io::Result<()> = (|| {
+        match matches.value_of("format") {
+            Some("json") => {
+                writeln!(w, "{{")?;
+                writeln!(
+                    w,
+                    "  \"version\": [{}],",
+                    data.version.map(|v| v.to_string()).join(", ")
+                )?;
+                writeln!(
+                    w,
+                    "  \"module_name\": {},",
+                    json_string(&String::from_utf8_lossy(&data.module_name_raw))
+                )?;
+                writeln!(w, "  \"ptr_size\": {},", data.ptr_size)?;
+                writeln!(w, "  \"addresses\": [")?;
+                for (i, (id, offset)) in entries.iter().enumerate() {
+                    let sep = if i + 1 < entries.len() { "," } else { "" };
+                    match names.name(*id) {
+                        Some(name) => writeln!(
+                            w,
+                            "    {{\"id\": {}, \"offset\": {}, \"name\": {}}}{}",
+                            id,
+                            offset,
+                            json_string(name),
+                            sep
+                        )?,
+                        None => {
+                            writeln!(w, "    {{\"id\": {}, \"offset\": {}}}{}", id, offset, sep)?
+                        }
+                    }
+                }
+                writeln!(w, "  ]")?;
+                writeln!(w, "}}")?;
+            }
+            _ if names.is_empty() => {
+                writeln!(w, "id,offset")?;
+                for (id, offset) in entries.iter() {
+                    writeln!(w, "{},{:#x}", id, offset)?;
+                }
+            }
+            _ => {
+                writeln!(w, "id,offset,name")?;
+                for (id, offset) in entries.iter() {
+                    writeln!(w, "{},{:#x},{}", id, offset, names.name(*id).unwrap_or(""))?;
+                }
+            }
+        }
+        w.flush()
+    })();
+    match result {
+        // `versionlib dump | head` closes the pipe early
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| e.to_string()),
+    }
+}
+
+// Finds `<module_name>+<hex>` (with or without 0x) and returns the offsets.
+fn find_module_offsets(line: &str, module_name: &str) -> Vec<u64> {
+    let pattern = format!("{}+", module_name.to_ascii_lowercase());
+    let lower = line.to_ascii_lowercase();
+    let mut offsets = Vec::new();
+    let mut rest = lower.as_str();
+    while let Some(pos) = rest.find(&pattern) {
+        rest = &rest[pos + pattern.len()..];
+        let digits = rest.strip_prefix("0x").unwrap_or(rest);
+        let end = digits
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(digits.len());
+        if let Ok(offset) = u64::from_str_radix(&digits[..end], 16) {
+            offsets.push(offset);
+        }
+    }
+    offsets
+}
+
+fn symbolize(matches: &clap::ArgMatches) -> Result<(), String> {
+    let data = load(matches)?;
+    let names = load_names(matches)?;
+    let index = OffsetIndex::new(&data);
+    let module_name = String::from_utf8_lossy(&data.module_name_raw).to_string();
+
+    let input: Box<dyn BufRead> = match matches.value_of("log") {
+        Some(path) => Box::new(std::io::BufReader::new(
+            std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => Box::new(std::io::BufReader::new(std::io::stdin())),
+    };
+    let stdout = std::io::stdout();
+    let mut w = std::io::BufWriter::new(stdout.lock());
+
+    let result: std::io::Result<()> = (|| {
+        for line in input.lines() {
+            let line = line?;
+            let annotations: Vec<String> = find_module_offsets(&line, &module_name)
+                .into_iter()
+                .map(|offset| match index.nearest_id(offset) {
+                    Some((id, 0)) => format!("ID {}", label(&names, id)),
+                    Some((id, delta)) => format!("ID {} + {:#x}", label(&names, id), delta),
+                    None => String::from("ID ?"),
+                })
+                .collect();
+            if annotations.is_empty() {
+                writeln!(w, "{}", line)?;
+            } else {
+                writeln!(w, "{}  [{}]", line, annotations.join(", "))?;
+            }
+        }
+        w.flush()
+    })();
+    result.map_err(|e| e.to_string())
+}
+
+fn describe(change: Option<Change>) -> String {
+    match change {
+        None => String::from("missing in both"),
+        Some(Change::Added { offset }) => format!("added     {:#x}", offset),
+        Some(Change::Removed { offset }) => format!("REMOVED   {:#x}", offset),
+        Some(Change::Moved { old, new }) => format!("moved     {:#x} -> {:#x}", old, new),
+        Some(Change::Unchanged { offset }) => format!("unchanged {:#x}", offset),
+    }
+}
+
+fn diff(matches: &clap::ArgMatches) -> Result<bool, String> {
+    let old_path = matches.value_of("old").unwrap();
+    let new_path = matches.value_of("new").unwrap();
+    let old = versionlib::open(old_path).map_err(|e| format!("{}: {}", old_path, e))?;
+    let new = versionlib::open(new_path).map_err(|e| format!("{}: {}", new_path, e))?;
+
+    // the ids of versionlib!() calls may be names from the addresses.toml next to the sources,
+    // which is optional unless given explicitly with --names
+    let names = match matches.value_of("src") {
+        Some(src) if !matches.is_present("names") => {
+            let path = std::path::Path::new(src)
+                .parent()
+                .unwrap_or_else(|| std::path::Path::new(""))
+                .join(NAMES_FILE);
+            if path.exists() {
+                let path = path.to_string_lossy();
+                AddressNames::load(&path).map_err(|e| format!("{}: {}", path, e))?
+            } else {
+                AddressNames::default()
+            }
+        }
+        _ => load_names(matches)?,
+    };
+
+    let mut calls = Vec::new();
+    for src in matches.values_of("src").into_iter().flatten() {
+        scan_versionlib_calls(std::path::Path::new(src), &mut calls)
+            .map_err(|e| format!("{}: {}", src, e))?;
+    }
+    let mut referenced = Vec::new();
+    for (arg, location) in calls {
+        let id = parse_id(&names, &arg).map_err(|e| format!("{}: {}", location, e))?;
+        referenced.push((id, location));
+    }
+    for id in matches.values_of("id").into_iter().flatten() {
+        referenced.push((parse_id(&names, id)?, String::from("--id")));
+    }
+
+    let changes = versionlib::diff(&old, &new);
+    let (mut added, mut removed, mut moved, mut unchanged) = (0, 0, 0, 0);
+    for (_, change) in changes.iter() {
+        match change {
+            Change::Added { .. } => added += 1,
+            Change::Removed { .. } => removed += 1,
+            Change::Moved { .. } => moved += 1,
+            Change::Unchanged { .. } => unchanged += 1,
+        }
+    }
+
+    println!(
+        "old: {} ({} ids)",
+        version_string(old.version),
+        old.data.len()
+    );
+    println!(
+        "new: {} ({} ids)",
+        version_string(new.version),
+        new.data.len()
+    );
+    println!(
+        "added: {}, removed: {}, moved: {}, unchanged: {}",
+        added, removed, moved, unchanged
+    );
+
+    if matches.is_present("all") {
+        println!();
+        for (id, change) in changes.iter() {
+            match change {
+                Change::Added { offset } => println!("+ {} {:#x}", label(&names, *id), offset),
+                Change::Removed { offset } => println!("- {} {:#x}", label(&names, *id), offset),
+                Change::Moved { old, new } => {
+                    println!("~ {} {:#x} -> {:#x}", label(&names, *id), old, new)
+                }
+                Change::Unchanged { .. } => {}
+            }
+        }
+    }
+
+    let mut survived = true;
+    if !referenced.is_empty() {
+        println!();
+        println!("referenced ids:");
+        for (id, location) in referenced.iter() {
+            let change = versionlib::compare(&old, &new, *id);
+            if !new.data.contains_key(id) {
+                survived = false;
+            }
+            println!(
+                "  {:<32} {:<40} {}",
+                label(&names, *id),
+                describe(change),
+                location
+            );
+        }
+    }
+
+    Ok(survived)
+}
+
+fn check(matches: &clap::ArgMatches) -> Result<bool, String> {
+    if !matches.is_present("names") {
+        return Err(String::from("check requires --names"));
+    }
+    let names = load_names(matches)?;
+
+    let mut found_all = true;
+    for path in matches.values_of("bin").unwrap() {
+        let data = versionlib::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let missing: Vec<String> = names
+            .iter()
+            .filter(|(_, id)| !data.data.contains_key(id))
+            .map(|(name, id)| format!("{} ({})", name, id))
+            .collect();
+        if missing.is_empty() {
+            println!("{}: ok ({} names)", path, names.len());
+        } else {
+            println!("{}: missing {}", path, missing.join(", "));
+            found_all = false;
+        }
+    }
+    Ok(found_all)
+}
+
+fn main() {
+    let matches = get_clap().get_matches();
+
+    let result = match matches.subcommand() {
+        ("info", Some(matches)) => info(matches).map(|_| true),
+        ("id", Some(matches)) => lookup_id(matches),
+        ("offset", Some(matches)) => lookup_offset(matches),
+        ("dump", Some(matches)) => dump(matches).map(|_| true),
+        ("diff", Some(matches)) => diff(matches),
+        ("symbolize", Some(matches)) => symbolize(matches).map(|_| true),
+        ("check", Some(matches)) => check(matches),
+        _ => unreachable!(),
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    }
+}
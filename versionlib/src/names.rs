@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+
+use crate::VersionlibError;
+
+/// Symbolic names for address ids, read from a TOML manifest such as
+///
+/// ```toml
+/// ConsoleLog_Print = 51109
+/// ```
+#[derive(Debug, Default)]
+pub struct AddressNames {
+    by_name: BTreeMap<String, u64>,
+    by_id: hashbrown::HashMap<u64, String>,
+}
+
+impl AddressNames {
+    pub fn load(filename: &str) -> Result<Self, VersionlibError> {
+        let s = std::fs::read_to_string(filename).map_err(|e| VersionlibError::Open {
+            reason: e.to_string(),
+        })?;
+        Self::parse(&s)
+    }
+
+    pub fn parse(s: &str) -> Result<Self, VersionlibError> {
+        let names_err = |reason: String| VersionlibError::Names { reason };
+
+        let table = match s.parse::<toml::Value>() {
+            Ok(toml::Value::Table(table)) => table,
+            Ok(_) => return Err(names_err(String::from("expected a table"))),
+            Err(e) => return Err(names_err(e.to_string())),
+        };
+
+        let mut names = AddressNames::default();
+        for (name, value) in table {
+            let id = value
+                .as_integer()
+                .and_then(|id| u64::try_from(id).ok())
+                .ok_or_else(|| {
+                    names_err(format!("{}: expected an address id, found {}", name, value))
+                })?;
+            if let Some(other) = names.by_id.get(&id) {
+                return Err(names_err(format!(
+                    "{} and {} have the same address id {}",
+                    other, name, id
+                )));
+            }
+            names.by_id.insert(id, name.clone());
+            names.by_name.insert(name, id);
+        }
+        Ok(names)
+    }
+
+    pub fn id(&self, name: &str) -> Option<u64> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn name(&self, id: u64) -> Option<&str> {
+        self.by_id.get(&id).map(String::as_str)
+    }
+
+    /// Iterates over `(name, id)` sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.by_name.iter().map(|(name, id)| (name.as_str(), *id))
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let names = AddressNames::parse(
+            "# comment\nConsoleLog_Print = 51109\nConsoleLog_Singleton = 401203\n",
+        )
+        .unwrap();
+        assert_eq!(2, names.len());
+        assert_eq!(Some(51109), names.id("ConsoleLog_Print"));
+        assert_eq!(Some("ConsoleLog_Singleton"), names.name(401203));
+        assert_eq!(None, names.id("Unknown"));
+
+        assert!(AddressNames::parse("A = 1\nB = 1\n").is_err());
+        assert!(AddressNames::parse("A = -1\n").is_err());
+        assert!(AddressNames::parse("A = \"1\"\n").is_err());
+    }
+}
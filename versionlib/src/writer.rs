@@ -0,0 +1,191 @@
+use std::{fs::File, io::BufWriter, io::Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{VersionlibData, VersionlibError};
+
+// Picks the shortest of the delta encodings understood by `load` for `value`,
+// given the previously written `prev`. Returns the 4-bit type and writes the payload.
+fn encode<W: Write>(w: &mut W, prev: u64, value: u64) -> std::io::Result<u8> {
+    if prev.checked_add(1) == Some(value) {
+        return Ok(1);
+    }
+    if value > prev && value - prev <= u8::MAX as u64 {
+        w.write_u8((value - prev) as u8)?;
+        return Ok(2);
+    }
+    if value < prev && prev - value <= u8::MAX as u64 {
+        w.write_u8((prev - value) as u8)?;
+        return Ok(3);
+    }
+    if value > prev && value - prev <= u16::MAX as u64 {
+        w.write_u16::<LittleEndian>((value - prev) as u16)?;
+        return Ok(4);
+    }
+    if value < prev && prev - value <= u16::MAX as u64 {
+        w.write_u16::<LittleEndian>((prev - value) as u16)?;
+        return Ok(5);
+    }
+    if value <= u16::MAX as u64 {
+        w.write_u16::<LittleEndian>(value as u16)?;
+        return Ok(6);
+    }
+    if value <= u32::MAX as u64 {
+        w.write_u32::<LittleEndian>(value as u32)?;
+        return Ok(7);
+    }
+    w.write_u64::<LittleEndian>(value)?;
+    Ok(0)
+}
+
+fn encoded_len(type_: u8) -> usize {
+    match type_ {
+        0 => 8,
+        1 => 0,
+        2 | 3 => 1,
+        4..=6 => 2,
+        7 => 4,
+        _ => unreachable!(),
+    }
+}
+
+/// Writes `data` in the format-2 delta encoding read by [`crate::load`].
+///
+/// Entries are written in ascending id order. `data.rdata` is not consulted.
+pub fn write<W: Write>(data: &VersionlibData, w: &mut W) -> Result<(), VersionlibError> {
+    let write_err = |e: std::io::Error| VersionlibError::Write {
+        reason: e.to_string(),
+    };
+
+    let tn_len = data.module_name_raw.len();
+    if tn_len >= 0x10000 {
+        return Err(VersionlibError::TnLenRange {
+            tn_len: tn_len as i32,
+        });
+    }
+    let addr_count = u32::try_from(data.data.len()).map_err(|e| VersionlibError::Write {
+        reason: e.to_string(),
+    })?;
+
+    w.write_u32::<LittleEndian>(2).map_err(write_err)?;
+    for v in data.version {
+        w.write_u32::<LittleEndian>(v).map_err(write_err)?;
+    }
+    w.write_i32::<LittleEndian>(tn_len as i32)
+        .map_err(write_err)?;
+    w.write_all(&data.module_name_raw).map_err(write_err)?;
+    w.write_u32::<LittleEndian>(data.ptr_size)
+        .map_err(write_err)?;
+    w.write_u32::<LittleEndian>(addr_count).map_err(write_err)?;
+
+    let mut entries: Vec<(u64, u64)> = data.data.iter().map(|(&k, &v)| (k, v)).collect();
+    entries.sort_unstable();
+
+    let ptr_size = data.ptr_size as u64;
+    let mut pvid: u64 = 0;
+    let mut poffset: u64 = 0;
+    let mut buf = Vec::with_capacity(16);
+
+    for (id, offset) in entries {
+        buf.clear();
+        let type_low = encode(&mut buf, pvid, id).map_err(write_err)?;
+
+        let mut offset_buf = Vec::with_capacity(8);
+        let mut type_high = encode(&mut offset_buf, poffset, offset).map_err(write_err)?;
+
+        // the offset may also be stored divided by the pointer size
+        if ptr_size != 0 && offset % ptr_size == 0 {
+            let mut scaled_buf = Vec::with_capacity(8);
+            let scaled_type = encode(&mut scaled_buf, poffset / ptr_size, offset / ptr_size)
+                .map_err(write_err)?;
+            if encoded_len(scaled_type) < encoded_len(type_high) {
+                type_high = scaled_type | 8;
+                offset_buf = scaled_buf;
+            }
+        }
+
+        w.write_u8(type_low | (type_high << 4)).map_err(write_err)?;
+        w.write_all(&buf).map_err(write_err)?;
+        w.write_all(&offset_buf).map_err(write_err)?;
+
+        pvid = id;
+        poffset = offset;
+    }
+
+    Ok(())
+}
+
+/// Writes `data` to `filename`. See [`write`].
+pub fn save(data: &VersionlibData, filename: &str) -> Result<(), VersionlibError> {
+    let f = File::create(filename).map_err(|e| VersionlibError::Create {
+        reason: e.to_string(),
+    })?;
+    let mut w = BufWriter::new(f);
+    write(data, &mut w)?;
+    w.flush().map_err(|e| VersionlibError::Write {
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load;
+
+    #[test]
+    fn round_trip() {
+        let out_dir = std::env::temp_dir().join(format!("versionlib-test-{}", std::process::id()));
+        std::fs::create_dir_all(&out_dir).unwrap();
+
+        let mut num_files = 0;
+        for entry in std::fs::read_dir("bin").unwrap() {
+            let path = entry.unwrap().path();
+            let original = load(path.to_str().unwrap()).unwrap();
+
+            let out_path = out_dir.join(path.file_name().unwrap());
+            save(&original, out_path.to_str().unwrap()).unwrap();
+            let result = load(out_path.to_str().unwrap()).unwrap();
+
+            assert_eq!(original.version, result.version, "{:?}", path);
+            assert_eq!(original.module_name_raw, result.module_name_raw);
+            assert_eq!(original.ptr_size, result.ptr_size);
+            assert_eq!(original.data, result.data, "{:?}", path);
+            assert_eq!(original.rdata, result.rdata, "{:?}", path);
+            num_files += 1;
+        }
+        assert!(num_files > 0);
+
+        std::fs::remove_dir_all(&out_dir).ok();
+    }
+
+    #[test]
+    fn trimmed() {
+        let original = load("bin/versionlib-1-6-323-0.bin").unwrap();
+        let ids = [
+            401203, 51109, 207886, 190143, 14720, 14617, 195816, 195890, 25259,
+        ];
+        let data: hashbrown::HashMap<u64, u64> =
+            ids.iter().map(|id| (*id, original.data[id])).collect();
+        let trimmed = VersionlibData {
+            version: original.version,
+            module_name: original.module_name.clone(),
+            module_name_raw: original.module_name_raw.clone(),
+            ptr_size: original.ptr_size,
+            rdata: data.iter().map(|(&k, &v)| (v, k)).collect(),
+            data,
+        };
+
+        let mut buf = Vec::new();
+        write(&trimmed, &mut buf).unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("versionlib-trimmed-{}.bin", std::process::id()));
+        std::fs::write(&path, &buf).unwrap();
+        let result = load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(ids.len(), result.data.len());
+        assert_eq!(0x8893c0, result.data[&51109]);
+        assert_eq!(25259, result.rdata[&0x398f70]);
+    }
+}